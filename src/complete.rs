@@ -0,0 +1,250 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::graph::Graph;
+use crate::help::{self, Level};
+use crate::{Arg, Error, SubCommand};
+
+/// The shells `Parser::render_completion` can generate a script for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// An identifier unique to the level, `app__remote__add`.
+fn id(level: &Level, bin: &str) -> String {
+    path_id(bin, &level.path)
+}
+
+/// The identifier of the level reached by `subcommand` from `level`.
+fn child_id(level: &Level, subcommand: &SubCommand, bin: &str) -> String {
+    let mut path = level.path.clone();
+    path.push(subcommand);
+    path_id(bin, &path)
+}
+
+fn path_id(bin: &str, path: &[&SubCommand]) -> String {
+    let mut parts = vec![bin];
+    parts.extend(path.iter().map(|subcommand| &*subcommand.name));
+    let id = parts.join("__");
+    id.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_")
+}
+
+/// Renders the completion script of `bin` for `shell`.
+pub(crate) fn render(graph: &Graph<Arg>, bin: &str, shell: Shell) -> Result<String, Error> {
+    let levels = help::levels(graph)?;
+    Ok(match shell {
+        Shell::Bash => bash(bin, &levels),
+        Shell::Zsh => zsh(bin, &levels),
+        Shell::Fish => fish(bin, &levels),
+    })
+}
+
+/// Names and aliases of a subcommand.
+fn names<'s>(subcommand: &'s SubCommand) -> Vec<&'s str> {
+    let mut names = vec![&*subcommand.name];
+    names.extend(subcommand.aliases.iter().map(|alias| &**alias));
+    names
+}
+
+fn bash(bin: &str, levels: &[Level]) -> String {
+    let root = id(&levels[0], bin);
+    let mut script = format!(
+        "_{root}() {{\n\
+         \x20   local cur prev cmd i opts\n\
+         \x20   COMPREPLY=()\n\
+         \x20   cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n\
+         \x20   prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n\
+         \x20   cmd=\"{root}\"\n\
+         \x20   for ((i = 1; i < COMP_CWORD; i++)); do\n\
+         \x20       case \"${{cmd}},${{COMP_WORDS[i]}}\" in\n",
+        root = root
+    );
+    for level in levels {
+        let id = id(level, bin);
+        for subcommand in &level.subcommands {
+            let patterns: Vec<String> = names(subcommand)
+                .iter()
+                .map(|name| format!("\"{},{}\"", id, name))
+                .collect();
+            script.push_str(&format!(
+                "            {}) cmd=\"{}\" ;;\n",
+                patterns.join("|"),
+                child_id(level, subcommand, bin)
+            ));
+        }
+    }
+    script.push_str("        esac\n    done\n    case \"${cmd}\" in\n");
+    for level in levels {
+        let mut words = vec![];
+        for (flag, _) in &level.flags {
+            words.push(format!("-{}", flag.short));
+            words.push(format!("--{}", flag.long));
+        }
+        for subcommand in &level.subcommands {
+            words.extend(names(subcommand).iter().map(|name| name.to_string()));
+        }
+        script.push_str(&format!("        {})\n", id(level, bin)));
+        script.push_str(&format!("            opts=\"{}\"\n", words.join(" ")));
+        let takes_arg: Vec<_> = level
+            .flags
+            .iter()
+            .filter(|(flag, _)| flag.takes_arg)
+            .collect();
+        if !takes_arg.is_empty() {
+            script.push_str("            case \"${prev}\" in\n");
+            for (flag, _) in takes_arg {
+                let reply = match flag.possible_values.is_empty() {
+                    true => "compgen -f -- \"${cur}\"".to_string(),
+                    false => format!(
+                        "compgen -W \"{}\" -- \"${{cur}}\"",
+                        flag.possible_values.join(" ")
+                    ),
+                };
+                script.push_str(&format!(
+                    "                -{}|--{}) COMPREPLY=($({})); return 0 ;;\n",
+                    flag.short, flag.long, reply
+                ));
+            }
+            script.push_str("            esac\n");
+        }
+        script.push_str("            COMPREPLY=($(compgen -W \"${opts}\" -- \"${cur}\"))\n");
+        script.push_str("            ;;\n");
+    }
+    script.push_str("    esac\n}\n\n");
+    script.push_str(&format!("complete -F _{} {}\n", root, bin));
+    script
+}
+
+/// Escapes a description for a single quoted zsh spec.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+fn zsh(bin: &str, levels: &[Level]) -> String {
+    let mut script = format!("#compdef {}\n", bin);
+    for level in levels {
+        let id = id(level, bin);
+        script.push_str(&format!("\n_{}() {{\n", id));
+        script.push_str("    local line state\n    _arguments -C \\\n");
+        for (flag, _) in &level.flags {
+            let mut spec = format!(
+                "        '(-{short} --{long})'{{-{short},--{long}}}'[{description}]",
+                short = flag.short,
+                long = flag.long,
+                description = zsh_escape(flag.description.as_deref().unwrap_or_default())
+            );
+            if flag.takes_arg {
+                spec.push_str(&format!(":{}:", flag.name.to_uppercase()));
+                match flag.possible_values.is_empty() {
+                    true => spec.push_str("_default"),
+                    false => spec.push_str(&format!("({})", flag.possible_values.join(" "))),
+                }
+            }
+            script.push_str(&format!("{}' \\\n", spec));
+        }
+        if level.subcommands.is_empty() {
+            script.push_str("        '*::arg:_default'\n}\n");
+            continue;
+        }
+        script.push_str("        '1: :->command' \\\n        '*:: :->args'\n");
+        script.push_str("    case $state in\n        command)\n");
+        let mut commands = vec![];
+        for subcommand in &level.subcommands {
+            let description = zsh_escape(subcommand.description.as_deref().unwrap_or_default());
+            let description = description.replace(':', "\\:");
+            for name in names(subcommand) {
+                commands.push(format!("'{}:{}'", name, description));
+            }
+        }
+        script.push_str(&format!(
+            "            local commands=({})\n",
+            commands.join(" ")
+        ));
+        script.push_str("            _describe command commands ;;\n        args)\n");
+        script.push_str("            case $line[1] in\n");
+        for subcommand in &level.subcommands {
+            script.push_str(&format!(
+                "                {}) _{} ;;\n",
+                names(subcommand).join("|"),
+                child_id(level, subcommand, bin)
+            ));
+        }
+        script.push_str("            esac ;;\n    esac\n}\n");
+    }
+    script.push_str(&format!("\n_{} \"$@\"\n", id(&levels[0], bin)));
+    script
+}
+
+/// Escapes a description for a single quoted fish argument.
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish(bin: &str, levels: &[Level]) -> String {
+    let mut script = String::new();
+    for (i, level) in levels.iter().enumerate() {
+        // a level is reached once each subcommand of its path has been seen
+        // and none of its own yet, the binary one while no subcommand has
+        let mut conditions: Vec<String> = level
+            .path
+            .iter()
+            .map(|subcommand| {
+                format!(
+                    "__fish_seen_subcommand_from {}",
+                    names(subcommand).join(" ")
+                )
+            })
+            .collect();
+        if i == 0 && !level.subcommands.is_empty() {
+            conditions.push("__fish_use_subcommand".to_string());
+        } else if !level.subcommands.is_empty() {
+            let children: Vec<&str> = level
+                .subcommands
+                .iter()
+                .flat_map(|sub| names(sub))
+                .collect();
+            conditions.push(format!(
+                "not __fish_seen_subcommand_from {}",
+                children.join(" ")
+            ));
+        }
+        let condition = match conditions.is_empty() {
+            true => String::new(),
+            false => format!(" -n '{}'", conditions.join("; and ")),
+        };
+        for subcommand in &level.subcommands {
+            for name in names(subcommand) {
+                let mut line = format!("complete -c {}{} -f -a {}", bin, condition, name);
+                if let Some(description) = &subcommand.description {
+                    line.push_str(&format!(" -d '{}'", fish_escape(description)));
+                }
+                script.push_str(&line);
+                script.push('\n');
+            }
+        }
+        for (flag, _) in &level.flags {
+            let mut line = format!(
+                "complete -c {}{} -s {} -l {}",
+                bin, condition, flag.short, flag.long
+            );
+            if flag.takes_arg {
+                line.push_str(" -r");
+                if !flag.possible_values.is_empty() {
+                    line.push_str(&format!(" -f -a '{}'", flag.possible_values.join(" ")));
+                }
+            }
+            if let Some(description) = &flag.description {
+                line.push_str(&format!(" -d '{}'", fish_escape(description)));
+            }
+            script.push_str(&line);
+            script.push('\n');
+        }
+    }
+    script
+}
@@ -0,0 +1,204 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::graph::GraphError;
+use std::error;
+use std::fmt;
+use std::process;
+
+/// Everything that can go wrong while declaring a parser or tapping it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A subcommand name is empty or contains a non word character.
+    InvalidName(String),
+    /// Two subcommands share a name or alias at the same level, `parent` is
+    /// `None` at the binary level.
+    DuplicateSubCommand {
+        name: String,
+        parent: Option<String>,
+    },
+    /// A flag matching none of the current level.
+    UnknownFlag(String),
+    /// An argument matching no subcommand nor declared positional.
+    UnknownArgument(String),
+    /// An unknown argument or long flag close to a subcommand name, alias
+    /// or flag of its level.
+    Misspelled { given: String, suggestion: String },
+    /// A required positional was not given.
+    MissingPositional(String),
+    /// A positional is declared after a variadic one, or is required but
+    /// declared after an optional one.
+    InvalidPositional(String),
+    /// A required flag was not given.
+    MissingFlag(String),
+    /// Two flags or positionals that cannot be used together were given.
+    Conflict(String, String),
+    /// The first was given without the second it requires.
+    MissingRequirement(String, String),
+    /// None of a group needing one was given.
+    MissingOneOf(Vec<String>),
+    /// A rule names no flag or positional of its level.
+    InvalidRule(String),
+    /// A flag's default value is not one of its possible values.
+    InvalidDefault { flag: String, value: String },
+    /// A flag rejecting repeats was given twice at the same level.
+    RepeatedFlag(String),
+    /// A flag taking a value was given none.
+    MissingValue(String),
+    /// A value could not be converted to the requested type, `arg` is the
+    /// flag or positional it was given to.
+    InvalidValue {
+        arg: String,
+        value: String,
+        reason: String,
+    },
+    /// A flag collecting values was given fewer than its minimum.
+    TooFewValues {
+        flag: String,
+        min: usize,
+        given: usize,
+    },
+    /// A flag collecting values was given more than its maximum.
+    TooManyValues {
+        flag: String,
+        max: usize,
+        given: usize,
+    },
+    /// A flag was given a value outside of its possible values.
+    InvalidChoice {
+        flag: String,
+        value: String,
+        choices: Vec<String>,
+    },
+    /// A flag not taking a value was given one.
+    UnexpectedValue { flag: String, value: String },
+    /// Help was requested, holds the text to print.
+    Help(String),
+    /// The version was requested, holds the text to print.
+    Version(String),
+    /// The license was requested, holds the text to print.
+    License(String),
+    /// The parser graph is inconsistent, this is a bug in tap.
+    Internal(String),
+}
+
+impl Error {
+    /// Whether the error comes from the parser declaration rather than from
+    /// the command line.
+    pub fn is_spec_error(&self) -> bool {
+        matches!(
+            self,
+            Error::InvalidName(_)
+                | Error::DuplicateSubCommand { .. }
+                | Error::InvalidPositional(_)
+                | Error::InvalidRule(_)
+                | Error::InvalidDefault { .. }
+                | Error::Internal(_)
+        )
+    }
+
+    /// Prints the error and exits the process with status 2. Requested
+    /// output like help goes to stdout with status 0.
+    pub fn exit(&self) -> ! {
+        if let Error::Help(text) | Error::Version(text) | Error::License(text) = self {
+            print!("{}", text);
+            process::exit(0);
+        }
+        eprintln!("error: {}", self);
+        process::exit(2);
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidName(name) => write!(f, "invalid subcommand name `{}`", name),
+            Error::DuplicateSubCommand { name, parent } => match parent {
+                Some(parent) => write!(
+                    f,
+                    "subcommand `{}` is declared twice under `{}`",
+                    name, parent
+                ),
+                None => write!(f, "subcommand `{}` is declared twice", name),
+            },
+            Error::UnknownFlag(flag) => write!(f, "unknown flag `{}`", flag),
+            Error::UnknownArgument(arg) => write!(f, "unexpected argument `{}`", arg),
+            Error::Misspelled { given, suggestion } => {
+                let kind = match given.starts_with('-') {
+                    true => "flag",
+                    false => "argument",
+                };
+                write!(
+                    f,
+                    "unknown {} `{}`, did you mean `{}`?",
+                    kind, given, suggestion
+                )
+            }
+            Error::MissingPositional(name) => {
+                write!(f, "missing required argument `<{}>`", name.to_uppercase())
+            }
+            Error::InvalidPositional(name) => write!(
+                f,
+                "positional `{}` cannot follow a variadic or an optional one",
+                name
+            ),
+            Error::MissingFlag(flag) => write!(f, "missing required flag `{}`", flag),
+            Error::Conflict(a, b) => write!(f, "`{}` cannot be used with `{}`", a, b),
+            Error::MissingRequirement(a, b) => write!(f, "`{}` requires `{}`", a, b),
+            Error::MissingOneOf(names) => {
+                let names: Vec<String> = names.iter().map(|name| format!("`{}`", name)).collect();
+                write!(f, "one of {} is required", names.join(", "))
+            }
+            Error::InvalidRule(name) => write!(f, "rule names unknown argument `{}`", name),
+            Error::InvalidDefault { flag, value } => write!(
+                f,
+                "default value `{}` of `{}` is not one of its possible values",
+                value, flag
+            ),
+            Error::RepeatedFlag(flag) => write!(f, "flag `{}` cannot be given twice", flag),
+            Error::MissingValue(flag) => write!(f, "flag `{}` requires a value", flag),
+            Error::InvalidValue { arg, value, reason } => {
+                write!(f, "invalid value `{}` for `{}`: {}", value, arg, reason)
+            }
+            Error::TooFewValues { flag, min, given } => write!(
+                f,
+                "flag `{}` takes at least {} values but {} were given",
+                flag, min, given
+            ),
+            Error::TooManyValues { flag, max, given } => write!(
+                f,
+                "flag `{}` takes at most {} values but {} were given",
+                flag, max, given
+            ),
+            Error::InvalidChoice {
+                flag,
+                value,
+                choices,
+            } => write!(
+                f,
+                "invalid value `{}` for `{}`, possible values: {}",
+                value,
+                flag,
+                choices.join(", ")
+            ),
+            Error::UnexpectedValue { flag, value } => write!(
+                f,
+                "flag `{}` does not take a value but `{}` was given",
+                flag, value
+            ),
+            Error::Help(text) | Error::Version(text) | Error::License(text) => {
+                write!(f, "{}", text)
+            }
+            Error::Internal(message) => write!(f, "internal error: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<GraphError> for Error {
+    fn from(error: GraphError) -> Self {
+        Error::Internal(error.to_string())
+    }
+}
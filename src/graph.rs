@@ -1,3 +1,4 @@
+use std::fmt;
 use std::ops::Index;
 
 #[derive(Debug, Copy, Clone)]
@@ -6,6 +7,23 @@ pub struct NodeIndex(pub usize);
 #[derive(Debug, Copy, Clone)]
 pub struct EdgeIndex(pub usize);
 
+#[derive(Debug, PartialEq)]
+pub enum GraphError {
+    InvalidIndex(NodeIndex),
+    InvalidEdge(NodeIndex, NodeIndex),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::InvalidIndex(index) => write!(f, "invalid node index {}", index.0),
+            GraphError::InvalidEdge(source, target) => {
+                write!(f, "invalid edge from {} to {}", source.0, target.0)
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Graph<T> {
     pub nodes: Vec<Node<T>>,
@@ -55,40 +73,54 @@ impl<T> Graph<T> {
         NodeIndex(index)
     }
 
-    pub fn add_node_to(&mut self, to: NodeIndex, data: T) -> NodeIndex {
+    pub fn add_node_to(&mut self, to: NodeIndex, data: T) -> Result<NodeIndex, GraphError> {
+        if to.0 >= self.nodes.len() {
+            return Err(GraphError::InvalidIndex(to));
+        }
         let index = self.add_node(data);
-        self.add_edge(to, index);
-        index
+        self.add_edge(to, index)?;
+        Ok(index)
     }
 
-    pub fn add_edge(&mut self, source: NodeIndex, target: NodeIndex) {
+    pub fn add_edge(&mut self, source: NodeIndex, target: NodeIndex) -> Result<(), GraphError> {
         if self.nodes.len() < 2
             || source == target
             || source.0 >= self.nodes.len()
             || target.0 >= self.nodes.len()
         {
-            panic!("invalid edge");
+            return Err(GraphError::InvalidEdge(source, target));
         }
         if self
             .edges
             .iter()
             .any(|edge| edge.source == source && edge.target == target)
         {
-            panic!("invalid edge");
+            return Err(GraphError::InvalidEdge(source, target));
         }
         let index = self.edges.len();
         let node_source = &self.nodes[source];
         self.edges
             .push(Edge::new(source, target, node_source.first_edge));
         self.nodes[source.0].first_edge = Some(EdgeIndex(index));
+        Ok(())
     }
 
-    pub fn successors(&self, source: Option<NodeIndex>) -> Successors {
-        Successors::new(&self, source)
+    pub fn successors(&self, source: Option<NodeIndex>) -> Result<Successors<'_>, GraphError> {
+        Successors::new(self, source)
     }
 
-    pub fn ancestors(&self, source: NodeIndex) -> Ancestors {
-        Ancestors::new(&self, source)
+    /// Successors sorted by index, that is in insertion order.
+    pub fn ordered_successors(
+        &self,
+        source: Option<NodeIndex>,
+    ) -> Result<Vec<NodeIndex>, GraphError> {
+        let mut indexes: Vec<NodeIndex> = self.successors(source)?.collect();
+        indexes.sort_by_key(|index| index.0);
+        Ok(indexes)
+    }
+
+    pub fn ancestors(&self, source: NodeIndex) -> Result<Ancestors, GraphError> {
+        Ancestors::new(self, source)
     }
 }
 
@@ -100,31 +132,31 @@ pub struct Successors<'a> {
 }
 
 impl<'a> Successors<'a> {
-    fn new<T>(graph: &'a Graph<T>, source: Option<NodeIndex>) -> Self {
+    fn new<T>(graph: &'a Graph<T>, source: Option<NodeIndex>) -> Result<Self, GraphError> {
         if let Some(index) = source {
             if index.0 >= graph.nodes.len() {
-                panic!("invalid index");
+                return Err(GraphError::InvalidIndex(index));
             }
             let first_outgoing_edge = graph.nodes[index].first_edge;
-            Successors {
+            Ok(Successors {
                 edges: Some(&graph.edges),
                 current_edge_index: first_outgoing_edge,
                 roots: None,
                 current_root_index: 0,
-            }
+            })
         } else {
             let mut roots = vec![];
             for (i, _) in graph.nodes.iter().enumerate() {
-                if let false = graph.edges.iter().any(|edge| edge.target == NodeIndex(i)) {
+                if !graph.edges.iter().any(|edge| edge.target == NodeIndex(i)) {
                     roots.push(NodeIndex(i));
                 }
             }
-            Successors {
+            Ok(Successors {
                 edges: None,
                 current_edge_index: None,
                 roots: Some(roots),
                 current_root_index: 0,
-            }
+            })
         }
     }
 }
@@ -167,20 +199,20 @@ pub struct Ancestors {
 }
 
 impl Ancestors {
-    fn new<T>(graph: &Graph<T>, from: NodeIndex) -> Self {
+    fn new<T>(graph: &Graph<T>, from: NodeIndex) -> Result<Self, GraphError> {
         if from.0 >= graph.nodes.len() {
-            panic!("invalid index");
+            return Err(GraphError::InvalidIndex(from));
         }
         let mut data = vec![];
         for edge in graph.edges.iter().filter(|edge| edge.target == from) {
-            if data.iter().find(|&&index| index == edge.source).is_none() && edge.source != from {
+            if !data.contains(&edge.source) && edge.source != from {
                 data.push(edge.source);
             }
         }
-        Ancestors {
+        Ok(Ancestors {
             data,
             current_index: 0,
-        }
+        })
     }
 }
 
@@ -268,8 +300,8 @@ mod tests {
     fn successors_new() {
         let mut graph = Graph::<Dummy>::new();
         let one = graph.add_node(Dummy("one"));
-        let two = graph.add_node_to(one, Dummy("two"));
-        let successors = Successors::new(&graph, Some(one));
+        let two = graph.add_node_to(one, Dummy("two")).unwrap();
+        let successors = Successors::new(&graph, Some(one)).unwrap();
         assert_eq!(successors.edges.unwrap().len(), 1);
         assert_eq!(successors.edges.unwrap()[0].source, one);
         assert_eq!(successors.edges.unwrap()[0].target, two);
@@ -282,9 +314,9 @@ mod tests {
     fn successors_new_in_root_mod() {
         let mut graph = Graph::<Dummy>::new();
         let one = graph.add_node(Dummy("one"));
-        graph.add_node_to(one, Dummy("two"));
-        let successors = Successors::new(&graph, None);
-        assert_eq!(successors.edges.is_none(), true);
+        graph.add_node_to(one, Dummy("two")).unwrap();
+        let successors = Successors::new(&graph, None).unwrap();
+        assert!(successors.edges.is_none());
         assert_eq!(successors.current_edge_index, None);
         let roots = successors.roots.unwrap();
         assert_eq!(roots.len(), 1);
@@ -308,33 +340,42 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn add_node_to_empty_graph() {
         let mut graph = Graph::<Dummy>::new();
-        graph.add_node_to(NodeIndex(0), Dummy("test"));
+        assert_eq!(
+            graph.add_node_to(NodeIndex(0), Dummy("test")),
+            Err(GraphError::InvalidIndex(NodeIndex(0)))
+        );
+        assert_eq!(graph.nodes.len(), 0);
     }
 
     #[test]
-    #[should_panic]
     fn add_node_to_invalid_index() {
         let mut graph = Graph::<Dummy>::new();
         graph.add_node(Dummy("one"));
-        graph.add_node_to(NodeIndex(1), Dummy("two"));
+        assert_eq!(
+            graph.add_node_to(NodeIndex(1), Dummy("two")),
+            Err(GraphError::InvalidIndex(NodeIndex(1)))
+        );
+        assert_eq!(graph.nodes.len(), 1);
     }
 
     #[test]
-    #[should_panic]
     fn add_node_to_invalid_index_2() {
         let mut graph = Graph::<Dummy>::new();
         graph.add_node(Dummy("one"));
-        graph.add_node_to(NodeIndex(42), Dummy("two"));
+        assert_eq!(
+            graph.add_node_to(NodeIndex(42), Dummy("two")),
+            Err(GraphError::InvalidIndex(NodeIndex(42)))
+        );
+        assert_eq!(graph.nodes.len(), 1);
     }
 
     #[test]
     fn adding_nodes_to() {
         let mut graph = Graph::<Dummy>::new();
         let one = graph.add_node(Dummy("one"));
-        let two = graph.add_node_to(one, Dummy("two"));
+        let two = graph.add_node_to(one, Dummy("two")).unwrap();
         assert_eq!(graph.nodes.len(), 2);
         assert_eq!(graph.edges.len(), 1);
         assert_eq!(graph.nodes[one].data.0, "one");
@@ -344,7 +385,7 @@ mod tests {
         assert_eq!(graph.edges[0].source, one);
         assert_eq!(graph.edges[0].target, two);
         assert_eq!(graph.edges[0].next_edge, None);
-        let three = graph.add_node_to(one, Dummy("three"));
+        let three = graph.add_node_to(one, Dummy("three")).unwrap();
         assert_eq!(graph.nodes.len(), 3);
         assert_eq!(graph.edges.len(), 2);
         assert_eq!(graph.nodes[one].data.0, "one");
@@ -362,47 +403,58 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn add_edge_on_empty_graph() {
         let mut graph = Graph::<Dummy>::new();
-        graph.add_edge(NodeIndex(0), NodeIndex(1));
+        assert_eq!(
+            graph.add_edge(NodeIndex(0), NodeIndex(1)),
+            Err(GraphError::InvalidEdge(NodeIndex(0), NodeIndex(1)))
+        );
     }
 
     #[test]
-    #[should_panic]
     fn add_edge_on_graph_with_one_node() {
         let mut graph = Graph::<Dummy>::new();
         let one = graph.add_node(Dummy("one"));
-        graph.add_edge(one, NodeIndex(1));
+        assert_eq!(
+            graph.add_edge(one, NodeIndex(1)),
+            Err(GraphError::InvalidEdge(one, NodeIndex(1)))
+        );
     }
 
     #[test]
-    #[should_panic]
     fn add_edge_with_two_equal_indexes() {
         let mut graph = Graph::<Dummy>::new();
         graph.add_node(Dummy("one"));
         graph.add_node(Dummy("two"));
-        graph.add_edge(NodeIndex(0), NodeIndex(0));
+        assert_eq!(
+            graph.add_edge(NodeIndex(0), NodeIndex(0)),
+            Err(GraphError::InvalidEdge(NodeIndex(0), NodeIndex(0)))
+        );
     }
 
     #[test]
-    #[should_panic]
     fn add_edge_with_invalid_index() {
         let mut graph = Graph::<Dummy>::new();
         graph.add_node(Dummy("one"));
         graph.add_node(Dummy("two"));
-        graph.add_edge(NodeIndex(0), NodeIndex(2));
+        assert_eq!(
+            graph.add_edge(NodeIndex(0), NodeIndex(2)),
+            Err(GraphError::InvalidEdge(NodeIndex(0), NodeIndex(2)))
+        );
     }
 
     #[test]
-    #[should_panic]
     fn add_same_edge_twice() {
         let mut graph = Graph::<Dummy>::new();
         let one = graph.add_node(Dummy("one"));
         let two = graph.add_node(Dummy("two"));
-        graph.add_edge(one, two);
+        graph.add_edge(one, two).unwrap();
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(
+            graph.add_edge(one, two),
+            Err(GraphError::InvalidEdge(one, two))
+        );
         assert_eq!(graph.edges.len(), 1);
-        graph.add_edge(one, two);
     }
 
     #[test]
@@ -410,14 +462,14 @@ mod tests {
         let mut graph = Graph::<Dummy>::new();
         let one = graph.add_node(Dummy("one"));
         let two = graph.add_node(Dummy("two"));
-        graph.add_edge(one, two);
+        graph.add_edge(one, two).unwrap();
         assert_eq!(graph.edges.len(), 1);
         assert_eq!(graph.edges[0].source, one);
         assert_eq!(graph.edges[0].target, two);
         assert_eq!(graph.edges[0].next_edge, None);
         assert_eq!(graph.nodes[one].first_edge, Some(EdgeIndex(0)));
         let three = graph.add_node(Dummy("three"));
-        graph.add_edge(one, three);
+        graph.add_edge(one, three).unwrap();
         assert_eq!(graph.edges.len(), 2);
         assert_eq!(graph.edges[0].source, one);
         assert_eq!(graph.edges[0].target, two);
@@ -434,19 +486,19 @@ mod tests {
         let one = graph.add_node(Dummy("one"));
         let two = graph.add_node(Dummy("two"));
         let three = graph.add_node(Dummy("three"));
-        let four = graph.add_node_to(one, Dummy("four"));
-        let five = graph.add_node_to(four, Dummy("five"));
+        let four = graph.add_node_to(one, Dummy("four")).unwrap();
+        let five = graph.add_node_to(four, Dummy("five")).unwrap();
         assert_eq!(graph.nodes.len(), 5);
         assert_eq!(graph.edges.len(), 2);
-        let mut successors = graph.successors(Some(one));
+        let mut successors = graph.successors(Some(one)).unwrap();
         assert_eq!(successors.next(), Some(four));
         assert_eq!(successors.next(), None);
-        let mut successors = graph.successors(Some(four));
+        let mut successors = graph.successors(Some(four)).unwrap();
         assert_eq!(successors.next(), Some(five));
         assert_eq!(successors.next(), None);
-        let successors = graph.successors(Some(two));
+        let successors = graph.successors(Some(two)).unwrap();
         assert_eq!(successors.count(), 0);
-        let successors = graph.successors(Some(three));
+        let successors = graph.successors(Some(three)).unwrap();
         assert_eq!(successors.count(), 0);
     }
 
@@ -454,31 +506,41 @@ mod tests {
     fn successors_on_circular_graph() {
         let mut graph = Graph::<Dummy>::new();
         let one = graph.add_node(Dummy("one"));
-        let child = graph.add_node_to(one, Dummy("child"));
-        graph.add_edge(child, one);
+        let child = graph.add_node_to(one, Dummy("child")).unwrap();
+        graph.add_edge(child, one).unwrap();
         assert_eq!(graph.nodes.len(), 2);
         assert_eq!(graph.edges.len(), 2);
-        let mut successors = graph.successors(Some(one));
+        let mut successors = graph.successors(Some(one)).unwrap();
         assert_eq!(successors.next(), Some(child));
         assert_eq!(successors.next(), None);
-        let mut successors = graph.successors(Some(child));
+        let mut successors = graph.successors(Some(child)).unwrap();
         assert_eq!(successors.next(), Some(one));
         assert_eq!(successors.next(), None);
     }
 
     #[test]
-    #[should_panic]
     fn successors_on_empty_graph() {
         let graph = Graph::<Dummy>::new();
-        graph.successors(Some(NodeIndex(0)));
+        assert!(graph.successors(Some(NodeIndex(0))).is_err());
     }
 
     #[test]
-    #[should_panic]
     fn successors_invalid_index() {
         let mut graph = Graph::<Dummy>::new();
         graph.add_node(Dummy("test"));
-        graph.successors(Some(NodeIndex(1)));
+        assert!(graph.successors(Some(NodeIndex(1))).is_err());
+    }
+
+    #[test]
+    fn ordered_successors() {
+        let mut graph = Graph::<Dummy>::new();
+        let one = graph.add_node(Dummy("one"));
+        let two = graph.add_node_to(one, Dummy("two")).unwrap();
+        let three = graph.add_node_to(one, Dummy("three")).unwrap();
+        let four = graph.add_node(Dummy("four"));
+        assert_eq!(graph.ordered_successors(Some(one)), Ok(vec![two, three]));
+        assert_eq!(graph.ordered_successors(None), Ok(vec![one, four]));
+        assert!(graph.ordered_successors(Some(NodeIndex(42))).is_err());
     }
 
     #[test]
@@ -487,11 +549,11 @@ mod tests {
         let one = graph.add_node(Dummy("one"));
         let two = graph.add_node(Dummy("two"));
         let three = graph.add_node(Dummy("three"));
-        let four = graph.add_node_to(one, Dummy("four"));
-        graph.add_node_to(four, Dummy("five"));
+        let four = graph.add_node_to(one, Dummy("four")).unwrap();
+        graph.add_node_to(four, Dummy("five")).unwrap();
         assert_eq!(graph.nodes.len(), 5);
         assert_eq!(graph.edges.len(), 2);
-        let mut roots = graph.successors(None);
+        let mut roots = graph.successors(None).unwrap();
         assert_eq!(roots.next(), Some(one));
         assert_eq!(roots.next(), Some(two));
         assert_eq!(roots.next(), Some(three));
@@ -502,34 +564,32 @@ mod tests {
     fn no_roots_on_circular_graph() {
         let mut graph = Graph::<Dummy>::new();
         let first = graph.add_node(Dummy("first"));
-        let second = graph.add_node_to(first, Dummy("second"));
-        graph.add_edge(second, first);
+        let second = graph.add_node_to(first, Dummy("second")).unwrap();
+        graph.add_edge(second, first).unwrap();
         assert_eq!(graph.nodes.len(), 2);
         assert_eq!(graph.edges.len(), 2);
-        let roots = graph.successors(None);
+        let roots = graph.successors(None).unwrap();
         assert_eq!(roots.count(), 0);
     }
 
     #[test]
     fn no_roots_on_empty_graph() {
         let graph = Graph::<Dummy>::new();
-        let roots = graph.successors(None);
+        let roots = graph.successors(None).unwrap();
         assert_eq!(roots.count(), 0);
     }
 
     #[test]
-    #[should_panic]
     fn ancestors_on_empty_graph() {
         let graph = Graph::<Dummy>::new();
-        graph.ancestors(NodeIndex(0));
+        assert!(graph.ancestors(NodeIndex(0)).is_err());
     }
 
     #[test]
-    #[should_panic]
     fn ancestors_invalid_index() {
         let mut graph = Graph::<Dummy>::new();
         graph.add_node(Dummy("test"));
-        graph.ancestors(NodeIndex(1));
+        assert!(graph.ancestors(NodeIndex(1)).is_err());
     }
 
     #[test]
@@ -538,21 +598,21 @@ mod tests {
         let one = graph.add_node(Dummy("one"));
         let two = graph.add_node(Dummy("two"));
         let three = graph.add_node(Dummy("three"));
-        let four = graph.add_node_to(one, Dummy("four"));
-        graph.add_edge(two, four);
-        graph.add_edge(three, four);
+        let four = graph.add_node_to(one, Dummy("four")).unwrap();
+        graph.add_edge(two, four).unwrap();
+        graph.add_edge(three, four).unwrap();
         assert_eq!(graph.nodes.len(), 4);
         assert_eq!(graph.edges.len(), 3);
-        let mut ancestors = graph.ancestors(four);
+        let mut ancestors = graph.ancestors(four).unwrap();
         assert_eq!(ancestors.next(), Some(one));
         assert_eq!(ancestors.next(), Some(two));
         assert_eq!(ancestors.next(), Some(three));
         assert_eq!(ancestors.next(), None);
-        let ancestors = graph.ancestors(one);
+        let ancestors = graph.ancestors(one).unwrap();
         assert_eq!(ancestors.count(), 0);
-        let ancestors = graph.ancestors(two);
+        let ancestors = graph.ancestors(two).unwrap();
         assert_eq!(ancestors.count(), 0);
-        let ancestors = graph.ancestors(three);
+        let ancestors = graph.ancestors(three).unwrap();
         assert_eq!(ancestors.count(), 0);
     }
 
@@ -560,14 +620,14 @@ mod tests {
     fn ancestors_on_circular_graph() {
         let mut graph = Graph::<Dummy>::new();
         let one = graph.add_node(Dummy("one"));
-        let child = graph.add_node_to(one, Dummy("child"));
-        graph.add_edge(child, one);
+        let child = graph.add_node_to(one, Dummy("child")).unwrap();
+        graph.add_edge(child, one).unwrap();
         assert_eq!(graph.nodes.len(), 2);
         assert_eq!(graph.edges.len(), 2);
-        let mut ancestors = graph.ancestors(child);
+        let mut ancestors = graph.ancestors(child).unwrap();
         assert_eq!(ancestors.next(), Some(one));
         assert_eq!(ancestors.next(), None);
-        let mut ancestors = graph.ancestors(one);
+        let mut ancestors = graph.ancestors(one).unwrap();
         assert_eq!(ancestors.next(), Some(child));
         assert_eq!(ancestors.next(), None);
     }
@@ -0,0 +1,315 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::graph::{Graph, NodeIndex};
+use crate::{env_var, Arg, ArgType, Error, Flag, Meta, Positional, SubCommand};
+
+/// The nodes of the subcommands leading to `level`, from the outermost.
+pub(crate) fn path_indexes(
+    graph: &Graph<Arg>,
+    level: Option<NodeIndex>,
+) -> Result<Vec<NodeIndex>, Error> {
+    let mut path = vec![];
+    let mut current = level;
+    while let Some(index) = current {
+        path.push(index);
+        current = graph.ancestors(index)?.next();
+    }
+    path.reverse();
+    Ok(path)
+}
+
+/// The subcommands leading to `level`, from the outermost.
+pub(crate) fn path<'g, 'a>(
+    graph: &'g Graph<Arg<'a>>,
+    level: Option<NodeIndex>,
+) -> Result<Vec<&'g SubCommand<'a>>, Error> {
+    let mut path = vec![];
+    for index in path_indexes(graph, level)? {
+        if let ArgType::SubCommand(subcommand) = &graph.nodes[index].data.kind {
+            path.push(subcommand);
+        }
+    }
+    Ok(path)
+}
+
+/// The flags accepted at `level`: its own ones in declaration order, then
+/// the global ones declared above it. Each comes with the depth of the
+/// subcommand it is declared at, `0` for the binary.
+pub(crate) fn flags<'g, 'a>(
+    graph: &'g Graph<Arg<'a>>,
+    level: Option<NodeIndex>,
+) -> Result<Vec<(&'g Flag<'a>, usize)>, Error> {
+    let mut flags: Vec<(&Flag, usize)> = vec![];
+    let mut current = level;
+    let mut depth = path_indexes(graph, level)?.len();
+    loop {
+        for index in graph.ordered_successors(current)? {
+            if let ArgType::Flag(flag) = &graph.nodes[index].data.kind {
+                let own = current == level;
+                if (own || flag.global) && !flags.iter().any(|(known, _)| known.name == flag.name) {
+                    flags.push((flag, depth));
+                }
+            }
+        }
+        match current {
+            Some(index) => current = graph.ancestors(index)?.next(),
+            None => break,
+        }
+        depth -= 1;
+    }
+    Ok(flags)
+}
+
+/// The positionals declared at `level`, in declaration order.
+pub(crate) fn positionals<'g, 'a>(
+    graph: &'g Graph<Arg<'a>>,
+    level: Option<NodeIndex>,
+) -> Result<Vec<&'g Positional<'a>>, Error> {
+    let mut positionals = vec![];
+    for index in graph.ordered_successors(level)? {
+        if let ArgType::Positional(positional) = &graph.nodes[index].data.kind {
+            positionals.push(positional);
+        }
+    }
+    Ok(positionals)
+}
+
+/// The subcommands declared at `level`, in declaration order.
+pub(crate) fn subcommands<'g, 'a>(
+    graph: &'g Graph<Arg<'a>>,
+    level: Option<NodeIndex>,
+) -> Result<Vec<(NodeIndex, &'g SubCommand<'a>)>, Error> {
+    let mut subcommands = vec![];
+    for index in graph.ordered_successors(level)? {
+        if let ArgType::SubCommand(subcommand) = &graph.nodes[index].data.kind {
+            subcommands.push((index, subcommand));
+        }
+    }
+    Ok(subcommands)
+}
+
+/// A subcommand level of the graph with what it declares.
+pub(crate) struct Level<'g, 'a> {
+    pub(crate) index: Option<NodeIndex>,
+    /// The subcommands leading to the level, empty for the binary.
+    pub(crate) path: Vec<&'g SubCommand<'a>>,
+    /// The flags accepted at the level with the depth they are declared at.
+    pub(crate) flags: Vec<(&'g Flag<'a>, usize)>,
+    pub(crate) positionals: Vec<&'g Positional<'a>>,
+    pub(crate) subcommands: Vec<&'g SubCommand<'a>>,
+}
+
+impl<'g, 'a> Level<'g, 'a> {
+    /// The names of the binary and of the subcommands leading to the level.
+    pub(crate) fn names<'s>(&'s self, bin: &'s str) -> Vec<&'s str> {
+        let mut names = vec![bin];
+        names.extend(self.path.iter().map(|subcommand| &*subcommand.name));
+        names
+    }
+}
+
+/// Every level of the graph, the binary first, then each subcommand before
+/// its children.
+pub(crate) fn levels<'g, 'a>(graph: &'g Graph<Arg<'a>>) -> Result<Vec<Level<'g, 'a>>, Error> {
+    let mut levels = vec![];
+    let mut stack = vec![(None, vec![])];
+    while let Some((index, path)) = stack.pop() {
+        let subcommands = subcommands(graph, index)?;
+        // pushed in reverse so they are visited in declaration order
+        for (child, subcommand) in subcommands.iter().rev() {
+            let mut path = path.clone();
+            path.push(*subcommand);
+            stack.push((Some(*child), path));
+        }
+        levels.push(Level {
+            index,
+            path,
+            flags: flags(graph, index)?,
+            positionals: positionals(graph, index)?,
+            subcommands: subcommands.into_iter().map(|(_, sub)| sub).collect(),
+        });
+    }
+    Ok(levels)
+}
+
+/// `-o, --output <OUTPUT>` or `-i, --include <INCLUDE>...`
+pub(crate) fn flag_usage(flag: &Flag) -> String {
+    let mut usage = format!("-{}, --{}", flag.short, flag.long);
+    if flag.takes_arg {
+        usage.push_str(&format!(" <{}>", flag.name.to_uppercase()));
+    }
+    if flag.multiple {
+        usage.push_str("...");
+    }
+    usage
+}
+
+/// `<INPUT>`, `[OUTPUT]` or `[FILES]...`
+pub(crate) fn positional_usage(positional: &Positional) -> String {
+    let name = positional.name.to_uppercase();
+    let mut usage = match positional.required {
+        true => format!("<{}>", name),
+        false => format!("[{}]", name),
+    };
+    if positional.variadic {
+        usage.push_str("...");
+    }
+    usage
+}
+
+/// Renders the help of the subcommand at `level`, or of the binary when
+/// `None`, in which case `description` is the binary one and the help starts
+/// with the program version and authors.
+pub(crate) fn render(
+    graph: &Graph<Arg>,
+    level: Option<NodeIndex>,
+    bin: &str,
+    description: Option<&str>,
+    meta: &Meta,
+    env_prefix: Option<&str>,
+) -> Result<String, Error> {
+    let path = path(graph, level)?;
+    let flags = flags(graph, level)?;
+    let positionals = positionals(graph, level)?;
+    let subcommands = subcommands(graph, level)?;
+    let mut help = String::new();
+    if level.is_none() {
+        if let Some(version) = &meta.version {
+            help.push_str(&format!("{} {}\n", bin, version));
+        }
+        if let Some(authors) = &meta.authors {
+            help.push_str(&format!("{}\n", authors.replace(':', ", ")));
+        }
+        if !help.is_empty() {
+            help.push('\n');
+        }
+    }
+    help.push_str(&format!("Usage: {}\n", usage(graph, level, bin)?));
+    let description = match path.last() {
+        Some(subcommand) => subcommand.description.as_deref(),
+        None => description,
+    };
+    if let Some(description) = description {
+        help.push_str(&format!("\n{}\n", description));
+    }
+    if !positionals.is_empty() {
+        help.push_str("\nArguments:\n");
+        let rows = positionals
+            .iter()
+            .map(|positional| {
+                (
+                    positional_usage(positional),
+                    positional
+                        .description
+                        .as_deref()
+                        .unwrap_or_default()
+                        .to_string(),
+                )
+            })
+            .collect();
+        push_table(&mut help, rows);
+    }
+    if !flags.is_empty() {
+        help.push_str("\nOptions:\n");
+        let rows = flags
+            .iter()
+            .map(|(flag, depth)| {
+                let details = flag_details(flag, &path[..*depth], env_prefix);
+                (flag_usage(flag), details)
+            })
+            .collect();
+        push_table(&mut help, rows);
+    }
+    if !subcommands.is_empty() {
+        help.push_str("\nCommands:\n");
+        let rows = subcommands
+            .iter()
+            .map(|(_, subcommand)| (subcommand.name.to_string(), subcommand_details(subcommand)))
+            .collect();
+        push_table(&mut help, rows);
+    }
+    Ok(help)
+}
+
+/// `app remote [OPTIONS] <NAME> [COMMAND]`
+pub(crate) fn usage(
+    graph: &Graph<Arg>,
+    level: Option<NodeIndex>,
+    bin: &str,
+) -> Result<String, Error> {
+    let mut usage = vec![bin.to_string()];
+    usage.extend(
+        path(graph, level)?
+            .iter()
+            .map(|subcommand| subcommand.name.to_string()),
+    );
+    if !flags(graph, level)?.is_empty() {
+        usage.push("[OPTIONS]".to_string());
+    }
+    for positional in positionals(graph, level)? {
+        usage.push(positional_usage(positional));
+    }
+    if !subcommands(graph, level)?.is_empty() {
+        usage.push("[COMMAND]".to_string());
+    }
+    Ok(usage.join(" "))
+}
+
+/// The description of a flag followed by its possible values, default
+/// value and environment variable. `path` leads to the level the flag is
+/// declared at.
+pub(crate) fn flag_details(flag: &Flag, path: &[&SubCommand], env_prefix: Option<&str>) -> String {
+    let mut details = flag.description.as_deref().unwrap_or_default().to_string();
+    if !flag.possible_values.is_empty() {
+        let choices = flag.possible_values.join(", ");
+        push_detail(&mut details, &format!("[possible values: {}]", choices));
+    }
+    if let Some(default) = &flag.default {
+        push_detail(&mut details, &format!("[default: {}]", default));
+    }
+    let names: Vec<&str> = path.iter().map(|subcommand| &*subcommand.name).collect();
+    if let Some(var) = env_var(flag, env_prefix, &names) {
+        push_detail(&mut details, &format!("[env: {}]", var));
+    }
+    details
+}
+
+/// The description of a subcommand followed by its aliases.
+pub(crate) fn subcommand_details(subcommand: &SubCommand) -> String {
+    let mut details = subcommand
+        .description
+        .as_deref()
+        .unwrap_or_default()
+        .to_string();
+    if !subcommand.aliases.is_empty() {
+        let aliases = format!("[aliases: {}]", subcommand.aliases.join(", "));
+        push_detail(&mut details, &aliases);
+    }
+    details
+}
+
+/// Appends a bracketed detail like `[default: auto]` to a description.
+fn push_detail(details: &mut String, detail: &str) {
+    if !details.is_empty() {
+        details.push(' ');
+    }
+    details.push_str(detail);
+}
+
+/// Pushes two aligned columns.
+fn push_table(help: &mut String, rows: Vec<(String, String)>) {
+    let width = rows
+        .iter()
+        .map(|(left, _)| left.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (left, right) in rows {
+        if right.is_empty() {
+            help.push_str(&format!("  {}\n", left));
+        } else {
+            help.push_str(&format!("  {:width$}  {}\n", left, right, width = width));
+        }
+    }
+}
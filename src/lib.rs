@@ -2,25 +2,130 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+mod complete;
+mod error;
 mod graph;
-use graph::{Edge, EdgeIndex, Graph, Node, NodeIndex};
+mod help;
+mod man;
+mod markdown;
+mod parsed;
+mod suggest;
+pub use complete::Shell;
+pub use error::Error;
+use graph::{Graph, NodeIndex};
+pub use parsed::{Opt, Parsed, Source};
 use regex::Regex;
+use std::borrow::Cow;
 use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::str;
+
+/// The built-in flags that print something and end parsing.
+#[derive(Debug, Clone, Copy)]
+enum Builtin {
+    Help,
+    Version,
+    License,
+}
 
 #[derive(Debug)]
 pub enum ArgType<'a> {
     Flag(Flag<'a>),
+    Positional(Positional<'a>),
+    Rule(Rule<'a>),
     SubCommand(SubCommand<'a>),
-    Argument(&'a str),
-    Unknown(&'a str),
-    UnknownFlag(&'a str),
+    Argument(OsString),
+    UnknownFlag(OsString),
     Over,
 }
 
+/// How tokens matching no flag or subcommand of the current level are
+/// handled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Fail with an error. A level with subcommands then takes no argument
+    /// besides its declared positionals.
+    Strict,
+    /// Collect unknown flags, see `Parsed::unknown`. An argument matching no
+    /// subcommand is kept as a positional one, see `Parsed::args`, so a
+    /// mistyped subcommand is only reported in `Mode::Strict`.
+    Lenient,
+    /// Keep unknown flags as positional arguments.
+    Positional,
+}
+
+/// A relationship between flags or positionals of the same level, named by
+/// their `name`, checked once parsing is done. A global flag declared above
+/// the level can be named too, given at any level. Values coming from a
+/// default do not count as given. A name matching nothing is reported as
+/// `Error::InvalidRule` on every tap. See `Rule::conflicts` and the other
+/// constructors to build one from `&str` or `String` names.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule<'a> {
+    /// The first cannot be given with the second.
+    Conflicts(Cow<'a, str>, Cow<'a, str>),
+    /// The first cannot be given without the second.
+    Requires(Cow<'a, str>, Cow<'a, str>),
+    /// Exactly one of them must be given.
+    ExactlyOne(Vec<Cow<'a, str>>),
+    /// At least one of them must be given.
+    AtLeastOne(Vec<Cow<'a, str>>),
+}
+
+impl<'a> Rule<'a> {
+    pub fn conflicts(a: impl Into<Cow<'a, str>>, b: impl Into<Cow<'a, str>>) -> Self {
+        Rule::Conflicts(a.into(), b.into())
+    }
+
+    pub fn requires(a: impl Into<Cow<'a, str>>, b: impl Into<Cow<'a, str>>) -> Self {
+        Rule::Requires(a.into(), b.into())
+    }
+
+    pub fn exactly_one<I>(names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Cow<'a, str>>,
+    {
+        Rule::ExactlyOne(names.into_iter().map(Into::into).collect())
+    }
+
+    pub fn at_least_one<I>(names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Cow<'a, str>>,
+    {
+        Rule::AtLeastOne(names.into_iter().map(Into::into).collect())
+    }
+
+    fn names(&self) -> Vec<&str> {
+        match self {
+            Rule::Conflicts(a, b) | Rule::Requires(a, b) => vec![a, b],
+            Rule::ExactlyOne(names) | Rule::AtLeastOne(names) => {
+                names.iter().map(|name| &**name).collect()
+            }
+        }
+    }
+}
+
+/// What happens when a flag is given several times at the same level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    /// Count the occurrences and keep every value, see `Opt::occurrences`.
+    Count,
+    /// Fail with an error.
+    Reject,
+    /// Keep the value of the last occurrence only.
+    LastWins,
+}
+
 #[derive(Debug)]
 pub struct Arg<'a> {
     kind: ArgType<'a>,
     found: bool,
+    occurrences: usize,
+    values: Vec<OsString>,
+    source: Source,
 }
 
 impl<'a> Arg<'a> {
@@ -28,65 +133,379 @@ impl<'a> Arg<'a> {
         Arg {
             kind: arg_type,
             found: false,
+            occurrences: 0,
+            values: vec![],
+            source: Source::CommandLine,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Flag<'a> {
-    name: &'a str,
+    name: Cow<'a, str>,
     short: char,
-    long: &'a str,
+    long: Cow<'a, str>,
     takes_arg: bool,
+    global: bool,
+    repeat: Repeat,
+    multiple: bool,
+    delimiter: Option<char>,
+    min_values: usize,
+    max_values: Option<usize>,
+    possible_values: Vec<Cow<'a, str>>,
+    ignore_case: bool,
+    default: Option<Cow<'a, str>>,
+    env: Option<Cow<'a, str>>,
+    required: bool,
+    description: Option<Cow<'a, str>>,
+    /// Set on the built-in flags only, a user flag may share their names.
+    builtin: Option<Builtin>,
 }
 
 impl<'a> Flag<'a> {
-    fn new(name: &'a str, short: char, long: &'a str, takes_arg: bool) -> Self {
+    /// Names and texts of a spec are either borrowed, like `&'static str`
+    /// literals, or owned `String`s built at runtime.
+    pub fn new(
+        name: impl Into<Cow<'a, str>>,
+        short: char,
+        long: impl Into<Cow<'a, str>>,
+        takes_arg: bool,
+    ) -> Self {
         Flag {
-            name,
+            name: name.into(),
             short,
-            long,
+            long: long.into(),
             takes_arg,
+            global: false,
+            repeat: Repeat::Count,
+            multiple: false,
+            delimiter: None,
+            min_values: 0,
+            max_values: None,
+            possible_values: vec![],
+            ignore_case: false,
+            default: None,
+            env: None,
+            required: false,
+            description: None,
+            builtin: None,
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<Cow<'a, str>>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Makes the flag accepted at any subcommand level below the one it is
+    /// declared at.
+    pub fn global(mut self) -> Self {
+        self.global = true;
+        self
+    }
+
+    /// Makes the flag take a value and collect the ones of every repeat,
+    /// `--include a --include b`.
+    pub fn multiple(mut self) -> Self {
+        self.takes_arg = true;
+        self.multiple = true;
+        self
+    }
+
+    /// Splits each value on `delimiter`, `--features a,b,c`. Implies
+    /// `multiple`.
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = Some(delimiter);
+        self.multiple()
+    }
+
+    /// The fewest values to collect when the flag is given. Implies
+    /// `multiple`.
+    pub fn min_values(mut self, min: usize) -> Self {
+        self.min_values = min;
+        self.multiple()
+    }
+
+    /// The most values to collect. Implies `multiple`.
+    pub fn max_values(mut self, max: usize) -> Self {
+        self.max_values = Some(max);
+        self.multiple()
+    }
+
+    /// Restricts the values to `values`, `--format json|yaml|text`. Implies
+    /// taking a value.
+    pub fn possible_values<I>(mut self, values: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Cow<'a, str>>,
+    {
+        self.takes_arg = true;
+        self.possible_values = values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Matches the possible values regardless of case, the value is then
+    /// reported as declared.
+    pub fn ignore_case(mut self) -> Self {
+        self.ignore_case = true;
+        self
+    }
+
+    /// The value used when the flag is not given, see `Opt::source`.
+    /// Implies taking a value. It is split and matched against the possible
+    /// values like a given one, one outside of them is reported as
+    /// `Error::InvalidDefault` on every tap.
+    pub fn default_value(mut self, default: impl Into<Cow<'a, str>>) -> Self {
+        self.takes_arg = true;
+        self.default = Some(default.into());
+        self
+    }
+
+    /// Reads the flag from the `name` environment variable when it is not
+    /// given on the command line. A flag taking no value is set by `1`,
+    /// `true`, `yes` or `on` and left unset by `0`, `false`, `no` or `off`,
+    /// any other value is an error.
+    pub fn env(mut self, name: impl Into<Cow<'a, str>>) -> Self {
+        self.env = Some(name.into());
+        self
+    }
+
+    /// Makes the flag mandatory whenever the level it is declared at is
+    /// reached.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Sets how repeats are handled, `Repeat::Count` by default.
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Splits a value given to the flag on its delimiter and checks each part
+    /// against the possible values.
+    fn split_value(&self, value: OsString) -> Result<Vec<OsString>, Error> {
+        let values = match self.delimiter {
+            Some(delimiter) => os_split(&value, delimiter),
+            None => vec![value],
+        };
+        if self.possible_values.is_empty() {
+            return Ok(values);
         }
+        values
+            .into_iter()
+            .map(|value| {
+                let choice = value.to_str().and_then(|value| {
+                    self.possible_values
+                        .iter()
+                        .find(|choice| match self.ignore_case {
+                            true => choice.eq_ignore_ascii_case(value),
+                            false => *choice == value,
+                        })
+                });
+                match choice {
+                    Some(choice) => Ok(OsString::from(choice.as_ref())),
+                    None => Err(Error::InvalidChoice {
+                        flag: format!("--{}", self.long),
+                        value: value.to_string_lossy().into_owned(),
+                        choices: self.possible_values.iter().map(|c| c.to_string()).collect(),
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    fn help() -> Self {
+        let mut flag = Flag::new("help", 'h', "help", false).description("Print help");
+        flag.builtin = Some(Builtin::Help);
+        flag
+    }
+
+    fn verbose() -> Self {
+        Flag::new("verbose", 'V', "verbose", false).description("Use verbose output")
+    }
+
+    fn version() -> Self {
+        let mut flag = Flag::new("version", 'v', "version", false).description("Print version");
+        flag.builtin = Some(Builtin::Version);
+        flag
+    }
+
+    fn license() -> Self {
+        let mut flag = Flag::new("license", 'L', "license", false).description("Print license");
+        flag.builtin = Some(Builtin::License);
+        flag
+    }
+
+    fn debug() -> Self {
+        Flag::new("debug", 'd', "debug", false).description("Print debug information")
     }
 }
 
-#[derive(Debug)]
-pub struct SubCommand<'a> {
-    name: &'a str,
-    aliases: Vec<&'a str>,
+/// A named positional argument, matched in declaration order among the
+/// positionals of its level.
+#[derive(Debug, Clone)]
+pub struct Positional<'a> {
+    name: Cow<'a, str>,
+    required: bool,
+    variadic: bool,
+    description: Option<Cow<'a, str>>,
 }
 
-impl<'a> SubCommand<'a> {
-    fn new(name: &'a str, aliases: Vec<&'a str>) -> Self {
-        SubCommand { name, aliases }
+impl<'a> Positional<'a> {
+    pub fn new(name: impl Into<Cow<'a, str>>, required: bool) -> Self {
+        Positional {
+            name: name.into(),
+            required,
+            variadic: false,
+            description: None,
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<Cow<'a, str>>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Makes the positional take every remaining argument, it has to be the
+    /// last one of its level.
+    pub fn variadic(mut self) -> Self {
+        self.variadic = true;
+        self
     }
 }
 
+#[derive(Debug)]
+pub struct SubCommand<'a> {
+    name: Cow<'a, str>,
+    aliases: Vec<Cow<'a, str>>,
+    description: Option<Cow<'a, str>>,
+    version: Option<Cow<'a, str>>,
+}
+
 #[derive(Debug)]
 pub struct Parser<'a> {
     graph: Graph<Arg<'a>>,
     binary_flags: Vec<Flag<'a>>,
+    positionals: Vec<Positional<'a>>,
+    rules: Vec<Rule<'a>>,
     subcommands: Vec<SubCommandConfig<'a>>,
     current_subcmd: Option<NodeIndex>,
+    mode: Mode,
+    description: Option<Cow<'a, str>>,
+    bin: Option<OsString>,
+    meta: Meta<'a>,
+    env_prefix: Option<Cow<'a, str>>,
+    requested: Option<Builtin>,
+}
+
+/// Information about the program, printed by the built-in flags. See
+/// `crate_meta!` to fill it from Cargo.
+#[derive(Debug, Clone, Default)]
+pub struct Meta<'a> {
+    name: Option<Cow<'a, str>>,
+    version: Option<Cow<'a, str>>,
+    authors: Option<Cow<'a, str>>,
+    license: Option<Cow<'a, str>>,
+}
+
+impl<'a> Meta<'a> {
+    pub fn new() -> Self {
+        Meta::default()
+    }
+
+    /// The program name, shown instead of the binary one.
+    pub fn name(mut self, name: impl Into<Cow<'a, str>>) -> Self {
+        self.name = non_empty(name);
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<Cow<'a, str>>) -> Self {
+        self.version = non_empty(version);
+        self
+    }
+
+    /// The authors, separated by `:` as in `CARGO_PKG_AUTHORS` or by `,`.
+    pub fn authors(mut self, authors: impl Into<Cow<'a, str>>) -> Self {
+        self.authors = non_empty(authors);
+        self
+    }
+
+    /// The license name or its full text.
+    pub fn license(mut self, license: impl Into<Cow<'a, str>>) -> Self {
+        self.license = non_empty(license);
+        self
+    }
+}
+
+fn non_empty<'a>(value: impl Into<Cow<'a, str>>) -> Option<Cow<'a, str>> {
+    Some(value.into()).filter(|value| !value.is_empty())
+}
+
+/// Builds a `Meta` from the `CARGO_PKG_*` variables of the calling crate.
+#[macro_export]
+macro_rules! crate_meta {
+    () => {
+        $crate::Meta::new()
+            .name(env!("CARGO_PKG_NAME"))
+            .version(env!("CARGO_PKG_VERSION"))
+            .authors(env!("CARGO_PKG_AUTHORS"))
+            .license(env!("CARGO_PKG_LICENSE"))
+    };
 }
 
 impl<'a> Parser<'a> {
-    fn new() -> Self {
+    pub fn new() -> Self {
         let graph = Graph::<Arg>::new();
         Parser {
             graph,
             binary_flags: vec![],
+            positionals: vec![],
+            rules: vec![],
             subcommands: vec![],
             current_subcmd: None,
+            mode: Mode::Lenient,
+            description: None,
+            bin: None,
+            meta: Meta::default(),
+            env_prefix: None,
+            requested: None,
         }
     }
 
+    /// Sets the name, version, authors and license printed by the built-in
+    /// flags.
+    pub fn meta(&mut self, meta: Meta<'a>) -> &mut Self {
+        self.meta = meta;
+        self
+    }
+
+    pub fn description(&mut self, description: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Binds every flag taking a value to an environment variable named
+    /// after `prefix`, the subcommand path and the long name, as in
+    /// `APP_REMOTE_ADD_URL`. A name given with `Flag::env` wins.
+    pub fn env_prefix(&mut self, prefix: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets how unknown arguments and flags are handled, `Mode::Lenient` by
+    /// default.
+    pub fn mode(&mut self, mode: Mode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
     pub fn flag(
         &mut self,
-        name: &'a str,
+        name: impl Into<Cow<'a, str>>,
         short: char,
-        long: &'a str,
+        long: impl Into<Cow<'a, str>>,
         takes_arg: bool,
     ) -> &mut Self {
         self.binary_flags
@@ -94,470 +513,2146 @@ impl<'a> Parser<'a> {
         self
     }
 
+    pub fn add_flag(&mut self, flag: Flag<'a>) -> &mut Self {
+        self.binary_flags.push(flag);
+        self
+    }
+
+    pub fn positional(&mut self, name: impl Into<Cow<'a, str>>, required: bool) -> &mut Self {
+        self.positionals.push(Positional::new(name, required));
+        self
+    }
+
+    pub fn add_positional(&mut self, positional: Positional<'a>) -> &mut Self {
+        self.positionals.push(positional);
+        self
+    }
+
+    pub fn rule(&mut self, rule: Rule<'a>) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Adds `-h, --help`, printing the help of the level it is given at.
     pub fn help(&mut self) -> &mut Self {
-        self.binary_flags
-            .push(Flag::new("help", 'h', "help", false));
+        self.binary_flags.push(Flag::help());
         self
     }
 
     pub fn verbose(&mut self) -> &mut Self {
-        self.binary_flags
-            .push(Flag::new("verbose", 'V', "verbose", false));
+        self.binary_flags.push(Flag::verbose());
         self
     }
 
     pub fn version(&mut self) -> &mut Self {
-        self.binary_flags
-            .push(Flag::new("version", 'v', "version", false));
+        self.binary_flags.push(Flag::version());
         self
     }
 
     pub fn license(&mut self) -> &mut Self {
-        self.binary_flags
-            .push(Flag::new("license", 'L', "license", false));
+        self.binary_flags.push(Flag::license());
         self
     }
 
     pub fn debug(&mut self) -> &mut Self {
-        self.binary_flags
-            .push(Flag::new("debug", 'd', "debug", false));
+        self.binary_flags.push(Flag::debug());
         self
     }
 
+    /// Adds a subcommand. A name or alias already used at this level is
+    /// reported when tapping, see `try_subcommand` to catch it right away.
     pub fn subcommand(&mut self, subcommand: SubCommandConfig<'a>) -> &mut Self {
-        if let Some(_) = self
-            .subcommands
-            .iter()
-            .find(|&subcmd| subcmd.name == subcommand.name)
-        {
-            panic!("cannot have two subcommands with the same name at the same level");
-        }
         self.subcommands.push(subcommand);
         self
     }
 
-    pub fn tap(&mut self, args: Vec<&'a str>) -> &mut Self {
-        self.build_graph();
-        println!("graph builded");
-        self.iterate_args(args);
-        println!("{:#?}", self.graph);
-        self
+    pub fn try_subcommand(&mut self, subcommand: SubCommandConfig<'a>) -> Result<&mut Self, Error> {
+        check_duplicate(&self.subcommands, &subcommand, None)?;
+        Ok(self.subcommand(subcommand))
     }
 
-    fn build_graph(&mut self) -> &mut Self {
-        for flag in &self.binary_flags {
-            self.graph.add_node(Arg::new(ArgType::Flag(*flag)));
-        }
-        for subcommand in &self.subcommands {
-            iterate_subcommand_config(&mut self.graph, &subcommand, None);
-        }
-        self
+    /// Parses the arguments the process was started with. The first one, the
+    /// binary, is skipped and kept in `Parsed::bin`.
+    pub fn tap_env(&mut self) -> Parsed<'a> {
+        self.try_tap_env().unwrap_or_else(|error| error.exit())
     }
 
-    fn iterate_args(&mut self, args: Vec<&'a str>) {
-        let mut accept_opt = true;
-        while let Some(&arg) = args.iter().next() {
-            if arg == "-" {
-                // self.graph.add_node(ArgType::Argument(arg));
-            } else if arg == "--" {
-                // self.graph.add_node(ArgType::Over);
-                accept_opt = false;
-            } else if arg.len() > 2 && arg.starts_with("--") && accept_opt == true {
-                // self.parse_long_option(&arg);
-            } else if arg.len() > 1 && arg.starts_with("-") && accept_opt == true {
-                // self.parse_option(&arg);
-            } else {
-                if !self.handle_subcommand(arg) {
-                    let node_index;
-                    let data = Arg::new(ArgType::Argument(arg));
-                    if let Some(index) = self.current_subcmd {
-                        node_index = self.graph.add_node_to(index, data);
-                    } else {
-                        node_index = self.graph.add_node(data);
-                    }
-                    self.graph.nodes[node_index.0].data.found = true;
-                }
+    pub fn try_tap_env(&mut self) -> Result<Parsed<'a>, Error> {
+        self.try_tap_argv(env::args_os())
+    }
+
+    /// Parses a full argument vector, the binary first.
+    fn try_tap_argv<I>(&mut self, mut args: I) -> Result<Parsed<'a>, Error>
+    where
+        I: Iterator<Item = OsString>,
+    {
+        self.bin = args.next();
+        let mut parsed = self.try_tap_os(args)?;
+        parsed.bin = self.bin.clone();
+        Ok(parsed)
+    }
+
+    /// Renders the help of the subcommand reached by `path`, names or
+    /// aliases, or of the binary when `path` is empty.
+    pub fn render_help(&mut self, path: &[&str]) -> Result<String, Error> {
+        self.build_graph()?;
+        for &name in path {
+            if !self.handle_subcommand(OsStr::new(name))? {
+                return Err(Error::UnknownArgument(name.to_string()));
             }
         }
+        self.help_text()
     }
 
-    fn handle_subcommand(&mut self, arg: &str) -> bool {
-        let mut children = self.graph.successors(self.current_subcmd);
-        let result = children.find(|index| {
-            if let ArgType::SubCommand(subcommand) = &self.graph.nodes[index.0].data.kind {
-                if subcommand.name == arg {
-                    return true;
-                }
-                if let Some(_) = subcommand.aliases.iter().find(|&&alias| alias == arg) {
-                    return true;
-                }
-            }
-            false
-        });
-        if let Some(index) = result {
-            self.graph.nodes[index.0].data.found = true;
-            self.current_subcmd = Some(index);
-            return true;
-        }
-        false
-    }
-
-    // fn parse_long_option(&mut self, arg: &str) {
-    // let current_arg = &arg[2..];
-    // let direct_children = self.graph.direct_children(self.current_subcmd);
-    // match current_arg.find("=") {
-    // None => {
-    // if let Some(i) = direct_children.iter().find(|index| {
-    // if let ArgType::Flag(flag) = &self.graph.nodes[index.0].data.kind {
-    // if flag.long == current_arg {
-    // return true;
-    // }
-    // }
-    // false
-    // }) {
-    // self.graph.nodes[i.0].data.found = true;
-    // } else {
-    // let index = self
-    // .graph
-    // .add_node(Arg::new(ArgType::UnknownFlag(current_arg)));
-    // if let Some(i) = self.current_subcmd {
-    // self.graph.add_edge(i, index);
-    // }
-    // }
-    // }
-    // Some(i) => {
-    // let first = &current_arg[..i];
-    // let last = &current_arg[i + 1..];
-    // if let Some(i) = direct_children.iter().find(|index| {
-    // if let ArgType::Flag(flag) = &self.graph.nodes[index.0].data.kind {
-    // if flag.long == first {
-    // return true;
-    // }
-    // }
-    // false
-    // }) {
-    // // if option.3 == true && !last.is_empty() {
-    // // tokens.push(Token::Option(&option, Some(String::from(last))));
-    // // } else {
-    // // tokens.push(Token::Option(&option, None));
-    // // }
-    // } else {
-    // let index = self
-    // .graph
-    // .add_node(Arg::new(ArgType::UnknownFlag(current_arg)));
-    // if let Some(i) = self.current_subcmd {
-    // self.graph.add_edge(i, index);
-    // }
-    // }
-    // }
-    // }
-    // }
-}
+    /// Renders a completion script for `shell` covering every subcommand
+    /// and flag.
+    pub fn render_completion(&mut self, shell: Shell) -> Result<String, Error> {
+        self.build_graph()?;
+        complete::render(&self.graph, &self.bin_name(), shell)
+    }
 
-fn iterate_subcommand_config<'a>(
-    graph: &mut Graph<Arg<'a>>,
-    current_subcmd: &SubCommandConfig<'a>,
-    previous_index: Option<NodeIndex>,
-) {
-    let subcmd_index;
-    let data = Arg::new(ArgType::SubCommand(SubCommand::from(current_subcmd)));
-    if let Some(index) = previous_index {
-        subcmd_index = graph.add_node_to(index, data);
-    } else {
-        subcmd_index = graph.add_node(data);
+    /// Renders the roff man page of the binary.
+    pub fn render_man(&mut self) -> Result<String, Error> {
+        self.build_graph()?;
+        let mut pages = self.man_pages(false)?;
+        Ok(pages.remove(0).1)
     }
-    for flag in &current_subcmd.flags {
-        graph.add_node_to(subcmd_index, Arg::new(ArgType::Flag(*flag)));
+
+    /// Renders the man page of the binary followed by one per subcommand,
+    /// each with its file name like `app-remote-add.1`.
+    pub fn render_man_pages(&mut self) -> Result<Vec<(String, String)>, Error> {
+        self.build_graph()?;
+        self.man_pages(true)
     }
-    for subcommand in &current_subcmd.subcommands {
-        iterate_subcommand_config(graph, subcommand, Some(subcmd_index));
+
+    /// Renders a Markdown reference of the command tree, a section per
+    /// subcommand.
+    pub fn render_markdown(&mut self) -> Result<String, Error> {
+        self.build_graph()?;
+        markdown::render(
+            &self.graph,
+            &self.bin_name(),
+            self.description.as_deref(),
+            self.env_prefix.as_deref(),
+        )
     }
-}
 
-#[derive(Debug)]
-pub struct SubCommandConfig<'a> {
-    flags: Vec<Flag<'a>>,
-    name: &'a str,
-    aliases: Vec<&'a str>,
-    subcommands: Vec<SubCommandConfig<'a>>,
-}
+    fn man_pages(&self, nested: bool) -> Result<Vec<(String, String)>, Error> {
+        let bin = self.bin_name();
+        man::render(
+            &self.graph,
+            &bin,
+            self.description.as_deref(),
+            &self.meta,
+            self.env_prefix.as_deref(),
+            nested,
+        )
+    }
 
-impl<'a> SubCommandConfig<'a> {
-    fn with_name(name: &'a str) -> Self {
-        if name.is_empty() || Regex::new(r"\W").unwrap().is_match(name) {
-            panic!("a subcommand must be defined with a valid name");
+    /// The help of the current level.
+    fn help_text(&self) -> Result<String, Error> {
+        help::render(
+            &self.graph,
+            self.current_subcmd,
+            &self.bin_name(),
+            self.description.as_deref(),
+            &self.meta,
+            self.env_prefix.as_deref(),
+        )
+    }
+
+    /// `name version`, the version being the one of the innermost subcommand
+    /// overriding it if any.
+    fn version_text(&self) -> Result<String, Error> {
+        let path = help::path(&self.graph, self.current_subcmd)?;
+        let mut text = self.bin_name();
+        let version = path.iter().rev().find_map(|subcommand| {
+            let version = subcommand.version.as_deref()?;
+            Some((version, &subcommand.name))
+        });
+        match version {
+            Some((version, name)) => {
+                text.push_str(&format!(" {} {}", name, version));
+            }
+            None => {
+                if let Some(version) = &self.meta.version {
+                    text.push_str(&format!(" {}", version));
+                }
+            }
         }
-        SubCommandConfig {
-            flags: vec![],
-            name,
-            subcommands: vec![],
-            aliases: vec![],
+        text.push('\n');
+        Ok(text)
+    }
+
+    fn license_text(&self) -> String {
+        match &self.meta.license {
+            Some(license) => format!("{}\n", license),
+            None => format!("{}: no license specified\n", self.bin_name()),
         }
     }
 
-    pub fn alias(mut self, alias: &'a str) -> Self {
-        self.aliases.push(alias);
-        self
+    /// The binary name shown in help, the program name when set, otherwise
+    /// the running binary unless tapped with `tap_env`.
+    fn bin_name(&self) -> String {
+        if let Some(name) = &self.meta.name {
+            return name.to_string();
+        }
+        match &self.bin {
+            Some(bin) => file_name(bin),
+            None => env::args_os()
+                .next()
+                .map(|bin| file_name(&bin))
+                .unwrap_or_default(),
+        }
     }
 
-    pub fn flag(mut self, name: &'a str, short: char, long: &'a str, takes_arg: bool) -> Self {
-        self.flags.push(Flag::new(name, short, long, takes_arg));
-        self
+    /// Parses `args` against the flags and subcommands declared so far and
+    /// returns what was matched. `args` does not include the binary, see
+    /// `tap_env` to parse the process arguments. The parser can be tapped
+    /// several times, each call starts from a fresh graph. On error the
+    /// message is printed and the process exits with status 2, see `try_tap`
+    /// to handle it instead.
+    pub fn tap(&mut self, args: Vec<&str>) -> Parsed<'a> {
+        self.try_tap(args).unwrap_or_else(|error| error.exit())
     }
 
-    pub fn help(mut self) -> Self {
-        self.flags.push(Flag::new("help", 'h', "help", false));
-        self
+    pub fn try_tap(&mut self, args: Vec<&str>) -> Result<Parsed<'a>, Error> {
+        self.try_tap_os(args)
     }
 
-    pub fn verbose(mut self) -> Self {
-        self.flags.push(Flag::new("verbose", 'V', "verbose", false));
-        self
+    /// Like `tap` but for arguments that may not be valid UTF-8, as given by
+    /// `std::env::args_os`. Flag and subcommand names still have to be.
+    pub fn tap_os<I, T>(&mut self, args: I) -> Parsed<'a>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        self.try_tap_os(args).unwrap_or_else(|error| error.exit())
     }
 
-    pub fn version(mut self) -> Self {
-        self.flags.push(Flag::new("version", 'v', "version", false));
-        self
+    pub fn try_tap_os<I, T>(&mut self, args: I) -> Result<Parsed<'a>, Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        self.build_graph()?;
+        self.iterate_args(args.into_iter().map(Into::into))?;
+        Ok(Parsed::from(&self.graph))
     }
 
-    pub fn debug(mut self) -> Self {
-        self.flags.push(Flag::new("debug", 'd', "debug", false));
-        self
+    fn build_graph(&mut self) -> Result<&mut Self, Error> {
+        self.graph = Graph::new();
+        self.current_subcmd = None;
+        self.requested = None;
+        for flag in &self.binary_flags {
+            self.graph.add_node(Arg::new(ArgType::Flag(flag.clone())));
+        }
+        check_positionals(&self.positionals)?;
+        for positional in &self.positionals {
+            self.graph
+                .add_node(Arg::new(ArgType::Positional(positional.clone())));
+        }
+        for rule in &self.rules {
+            self.graph.add_node(Arg::new(ArgType::Rule(rule.clone())));
+        }
+        for (i, subcommand) in self.subcommands.iter().enumerate() {
+            check_duplicate(&self.subcommands[..i], subcommand, None)?;
+            iterate_subcommand_config(&mut self.graph, subcommand, None)?;
+        }
+        check_rule_names(&self.graph)?;
+        check_defaults(&self.graph)?;
+        Ok(self)
     }
 
-    pub fn subcommand(mut self, subcommand: SubCommandConfig<'a>) -> Self {
-        if let Some(_) = self
-            .subcommands
+    fn iterate_args<I>(&mut self, mut args: I) -> Result<(), Error>
+    where
+        I: Iterator<Item = OsString>,
+    {
+        let mut accept_opt = true;
+        while let Some(arg) = args.next() {
+            let bytes = arg.as_encoded_bytes();
+            // after `--` everything is positional, `-` usually stands for stdin
+            if !accept_opt || arg == "-" {
+                self.handle_argument(arg, !accept_opt)?;
+            } else if arg == "--" {
+                self.record(ArgType::Over)?;
+                accept_opt = false;
+            } else if bytes.len() > 2 && bytes.starts_with(b"--") {
+                self.parse_long_option(arg, &mut args)?;
+            } else if bytes.len() > 1 && bytes[0] == b'-' {
+                self.parse_option(arg, &mut args)?;
+            } else if self.handle_subcommand(&arg)? {
+                continue;
+            } else {
+                self.handle_argument(arg, false)?;
+            }
+            match self.requested {
+                Some(Builtin::Help) => return Err(Error::Help(self.help_text()?)),
+                Some(Builtin::Version) => return Err(Error::Version(self.version_text()?)),
+                Some(Builtin::License) => return Err(Error::License(self.license_text())),
+                None => {}
+            }
+        }
+        self.apply_fallbacks()?;
+        self.check_value_counts()?;
+        self.check_required_flags()?;
+        self.check_required_positionals()?;
+        self.check_rules()
+    }
+
+    /// The binary level followed by the matched subcommands.
+    fn levels(&self) -> Result<Vec<Option<NodeIndex>>, Error> {
+        let mut levels = vec![None];
+        let path = help::path_indexes(&self.graph, self.current_subcmd)?;
+        levels.extend(path.into_iter().map(Some));
+        Ok(levels)
+    }
+
+    /// Fills the flags left unset at the reached levels from their
+    /// environment variable, then from their default value.
+    fn apply_fallbacks(&mut self) -> Result<(), Error> {
+        let levels = self.levels()?;
+        let path = help::path(&self.graph, self.current_subcmd)?;
+        let path: Vec<Cow<str>> = path
             .iter()
-            .find(|&subcmd| subcmd.name == subcommand.name)
-        {
-            panic!("cannot have two subcommands with the same name at the same level");
+            .map(|subcommand| subcommand.name.clone())
+            .collect();
+        for (i, &level) in levels.iter().enumerate() {
+            for index in self.graph.ordered_successors(level)? {
+                let flag = match self.flag_at(index) {
+                    Some(flag) => flag.clone(),
+                    None => continue,
+                };
+                if self.is_given(&levels[i..], &flag.name)? {
+                    continue;
+                }
+                let from_env =
+                    env_var(&flag, self.env_prefix.as_deref(), &path[..i]).and_then(|var| {
+                        let value = env::var_os(&var).filter(|value| !value.is_empty())?;
+                        Some((var, value))
+                    });
+                let (values, source) = match (from_env, &flag.default) {
+                    (Some((var, value)), _) if !flag.takes_arg => match env_bool(&flag, &value)? {
+                        true => (vec![], Source::Env(var)),
+                        false => continue,
+                    },
+                    (Some((var, value)), _) => (flag.split_value(value)?, Source::Env(var)),
+                    (None, Some(default)) => (
+                        flag.split_value(OsString::from(&**default))?,
+                        Source::Default,
+                    ),
+                    (None, None) => continue,
+                };
+                let data = &mut self.graph.nodes[index.0].data;
+                data.found = true;
+                data.values = values;
+                data.source = source;
+            }
         }
-        self.subcommands.push(subcommand);
-        self
+        Ok(())
     }
-}
 
-impl<'a> From<&SubCommandConfig<'a>> for SubCommand<'a> {
-    fn from(subcmd: &SubCommandConfig<'a>) -> Self {
-        SubCommand {
-            name: subcmd.name,
-            aliases: subcmd.aliases.clone(),
+    /// Whether the flag was set at one of `levels`, a global flag may be
+    /// given below the level it is declared at.
+    fn is_given(&self, levels: &[Option<NodeIndex>], name: &str) -> Result<bool, Error> {
+        for &level in levels {
+            if let Some(index) = self.find_flag_at(level, |flag| flag.name == name)? {
+                if self.graph.nodes[index.0].data.found {
+                    return Ok(true);
+                }
+            }
         }
+        Ok(false)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Fails on the first rule of a reached level that does not hold.
+    fn check_rules(&self) -> Result<(), Error> {
+        let levels = self.levels()?;
+        for (i, &level) in levels.iter().enumerate() {
+            for index in self.graph.ordered_successors(level)? {
+                let rule = match &self.graph.nodes[index.0].data.kind {
+                    ArgType::Rule(rule) => rule,
+                    _ => continue,
+                };
+                let mut given = vec![];
+                let mut labels = vec![];
+                for name in rule.names() {
+                    let (label, set) = self.lookup(&levels, i, name)?;
+                    if set {
+                        given.push(label.clone());
+                    }
+                    labels.push(label);
+                }
+                let error = match rule {
+                    Rule::Conflicts(..) if given.len() == 2 => {
+                        Error::Conflict(labels[0].clone(), labels[1].clone())
+                    }
+                    Rule::Requires(..) if given == labels[..1] => {
+                        Error::MissingRequirement(labels[0].clone(), labels[1].clone())
+                    }
+                    Rule::ExactlyOne(_) if given.len() > 1 => {
+                        Error::Conflict(given[0].clone(), given[1].clone())
+                    }
+                    Rule::ExactlyOne(_) | Rule::AtLeastOne(_) if given.is_empty() => {
+                        Error::MissingOneOf(labels)
+                    }
+                    _ => continue,
+                };
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
 
-    #[test]
+    /// The label of the flag or positional `name` of the rule declared at
+    /// `levels[rule_level]`, `--long` or `<NAME>`, and whether it was given
+    /// rather than defaulted. A global flag may be declared above the rule
+    /// and given at any reached level.
+    fn lookup(
+        &self,
+        levels: &[Option<NodeIndex>],
+        rule_level: usize,
+        name: &str,
+    ) -> Result<(String, bool), Error> {
+        let mut label = None;
+        let mut set = false;
+        for (depth, &level) in levels.iter().enumerate() {
+            for index in self.graph.ordered_successors(level)? {
+                let data = &self.graph.nodes[index.0].data;
+                let found = data.found && data.source != Source::Default;
+                match &data.kind {
+                    ArgType::Flag(flag)
+                        if flag.name == name && (depth == rule_level || flag.global) =>
+                    {
+                        label.get_or_insert_with(|| format!("--{}", flag.long));
+                        set |= found;
+                    }
+                    ArgType::Positional(positional)
+                        if positional.name == name && depth == rule_level =>
+                    {
+                        label.get_or_insert_with(|| help::positional_usage(positional));
+                        set |= found;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        match label {
+            Some(label) => Ok((label, set)),
+            None => Err(Error::InvalidRule(name.to_string())),
+        }
+    }
+
+    /// Fails on the first required flag not given at a reached level.
+    fn check_required_flags(&self) -> Result<(), Error> {
+        let levels = self.levels()?;
+        for (i, &level) in levels.iter().enumerate() {
+            for index in self.graph.ordered_successors(level)? {
+                let flag = match self.flag_at(index) {
+                    Some(flag) if flag.required => flag,
+                    _ => continue,
+                };
+                if !self.is_given(&levels[i..], &flag.name)? {
+                    return Err(Error::MissingFlag(format!("--{}", flag.long)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records a positional argument and fills the next declared positional
+    /// of the current level with it. An argument left over once the declared
+    /// positionals are filled is an error whatever the mode. Without declared
+    /// positionals, a level with subcommands takes none but `-` in
+    /// `Mode::Strict`. Arguments following `--` that fill no declared
+    /// positional are kept as trailing ones.
+    fn handle_argument(&mut self, arg: OsString, trailing: bool) -> Result<(), Error> {
+        let mut declared = false;
+        let mut slot = None;
+        for index in self.graph.ordered_successors(self.current_subcmd)? {
+            let data = &self.graph.nodes[index.0].data;
+            if let ArgType::Positional(positional) = &data.kind {
+                declared = true;
+                if !data.found || positional.variadic {
+                    slot = Some(index);
+                    break;
+                }
+            }
+        }
+        if let Some(index) = slot {
+            let data = &mut self.graph.nodes[index.0].data;
+            data.found = true;
+            data.values.push(arg.clone());
+        } else if !trailing
+            && (declared || (self.mode == Mode::Strict && arg != "-" && self.has_subcommands()?))
+        {
+            let given = arg.to_string_lossy().into_owned();
+            let error = match self.suggest_subcommand(&given)? {
+                Some(suggestion) => Error::Misspelled { given, suggestion },
+                None => Error::UnknownArgument(given),
+            };
+            return Err(error);
+        }
+        self.record(ArgType::Argument(arg))?;
+        Ok(())
+    }
+
+    /// Fails on the first required positional left empty at the binary level
+    /// or at a matched subcommand.
+    fn check_required_positionals(&self) -> Result<(), Error> {
+        for level in self.levels()? {
+            for index in self.graph.ordered_successors(level)? {
+                let data = &self.graph.nodes[index.0].data;
+                if let ArgType::Positional(positional) = &data.kind {
+                    if positional.required && !data.found {
+                        return Err(Error::MissingPositional(positional.name.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a cluster of short flags like `-hvd`. A flag taking an argument
+    /// consumes the rest of the cluster (`-ofile.txt`) or, when it ends the
+    /// cluster, the next argument (`-o file.txt`). In `Mode::Lenient` each
+    /// unknown flag of a cluster is collected on its own, otherwise the
+    /// cluster is rejected or kept as a whole.
+    fn parse_option<I>(&mut self, arg: OsString, args: &mut I) -> Result<(), Error>
+    where
+        I: Iterator<Item = OsString>,
+    {
+        let bytes = &arg.as_encoded_bytes()[1..];
+        // flags are looked up in the UTF-8 part only, a value may be anything
+        let cluster = match str::from_utf8(bytes) {
+            Ok(cluster) => cluster,
+            Err(error) => str::from_utf8(&bytes[..error.valid_up_to()]).unwrap_or_default(),
+        };
+        let mut flags = vec![];
+        let mut unknown = vec![];
+        let mut takes_value = false;
+        for (i, c) in cluster.char_indices() {
+            match self.find_flag(|flag| flag.short == c)? {
+                Some(index) => {
+                    flags.push((index, c, 1 + i + c.len_utf8()));
+                    if self.takes_arg(index) {
+                        takes_value = true;
+                        break;
+                    }
+                }
+                None => unknown.push(OsString::from(format!("-{}", c))),
+            }
+        }
+        if !takes_value && cluster.len() < bytes.len() {
+            let mut rest = OsString::from("-");
+            rest.push(os_suffix(&arg, 1 + cluster.len()));
+            unknown.push(rest);
+        }
+        if !unknown.is_empty() && self.mode != Mode::Lenient {
+            let error = Error::UnknownFlag(unknown[0].to_string_lossy().into_owned());
+            return self.unknown(arg, error);
+        }
+        for flag in unknown {
+            self.record(ArgType::UnknownFlag(flag))?;
+        }
+        for (index, c, end) in flags {
+            if !self.takes_arg(index) {
+                self.mark_flag(index, None)?;
+                continue;
+            }
+            let value = if end < arg.len() {
+                Some(os_suffix(&arg, end).to_os_string())
+            } else {
+                args.next()
+            };
+            match value {
+                Some(value) => self.mark_flag(index, Some(value))?,
+                None => return Err(Error::MissingValue(format!("-{}", c))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `--name`, `--name=value` and `--name value`.
+    fn parse_long_option<I>(&mut self, arg: OsString, args: &mut I) -> Result<(), Error>
+    where
+        I: Iterator<Item = OsString>,
+    {
+        let bytes = arg.as_encoded_bytes();
+        let (name, value) = match bytes.iter().position(|&byte| byte == b'=') {
+            Some(i) => (&bytes[2..i], Some(os_suffix(&arg, i + 1).to_os_string())),
+            None => (&bytes[2..], None),
+        };
+        let name = String::from_utf8_lossy(name).into_owned();
+        let index = match self.find_flag(|flag| flag.long == name)? {
+            Some(index) => index,
+            None => {
+                let given = format!("--{}", name);
+                let error = match self.suggest_flag(&name)? {
+                    Some(suggestion) => Error::Misspelled { given, suggestion },
+                    None => Error::UnknownFlag(given),
+                };
+                return self.unknown(arg, error);
+            }
+        };
+        if !self.takes_arg(index) {
+            if let Some(value) = value {
+                return Err(Error::UnexpectedValue {
+                    flag: format!("--{}", name),
+                    value: value.to_string_lossy().into_owned(),
+                });
+            }
+            return self.mark_flag(index, None);
+        }
+        // an empty value given explicitly, `--name=` or `--name ""`, is kept
+        match value.or_else(|| args.next()) {
+            Some(value) => self.mark_flag(index, Some(value)),
+            None => Err(Error::MissingValue(format!("--{}", name))),
+        }
+    }
+
+    /// Finds a flag declared at the current subcommand level or a global
+    /// one declared above it. A global flag gets a node at the current level
+    /// so it is reported where it was set.
+    fn find_flag<P>(&mut self, predicate: P) -> Result<Option<NodeIndex>, Error>
+    where
+        P: Fn(&Flag<'a>) -> bool,
+    {
+        if let Some(index) = self.find_flag_at(self.current_subcmd, &predicate)? {
+            return Ok(Some(index));
+        }
+        let mut level = self.current_subcmd;
+        while let Some(index) = level {
+            level = self.graph.ancestors(index)?.next();
+            let global = self.find_flag_at(level, |flag| flag.global && predicate(flag))?;
+            if let Some(flag) = global.and_then(|index| self.flag_at(index)) {
+                let data = Arg::new(ArgType::Flag(flag.clone()));
+                return Ok(Some(self.add_node(data)?));
+            }
+        }
+        Ok(None)
+    }
+
+    fn find_flag_at<P>(
+        &self,
+        level: Option<NodeIndex>,
+        predicate: P,
+    ) -> Result<Option<NodeIndex>, Error>
+    where
+        P: Fn(&Flag<'a>) -> bool,
+    {
+        let mut children = self.graph.successors(level)?;
+        Ok(children.find(|&index| self.flag_at(index).is_some_and(&predicate)))
+    }
+
+    fn flag_at(&self, index: NodeIndex) -> Option<&Flag<'a>> {
+        match &self.graph.nodes[index.0].data.kind {
+            ArgType::Flag(flag) => Some(flag),
+            _ => None,
+        }
+    }
+
+    fn takes_arg(&self, index: NodeIndex) -> bool {
+        self.flag_at(index).is_some_and(|flag| flag.takes_arg)
+    }
+
+    fn mark_flag(&mut self, index: NodeIndex, value: Option<OsString>) -> Result<(), Error> {
+        let flag = match self.flag_at(index) {
+            Some(flag) => flag.clone(),
+            None => return Err(Error::Internal(format!("node {} is not a flag", index.0))),
+        };
+        if flag.builtin.is_some() {
+            self.requested = flag.builtin;
+        }
+        let values = match value {
+            Some(value) => flag.split_value(value)?,
+            None => vec![],
+        };
+        let data = &mut self.graph.nodes[index.0].data;
+        if data.found {
+            match flag.repeat {
+                Repeat::Count => {}
+                Repeat::Reject => return Err(Error::RepeatedFlag(format!("--{}", flag.long))),
+                Repeat::LastWins => data.values.clear(),
+            }
+        }
+        data.found = true;
+        data.occurrences += 1;
+        data.values.extend(values);
+        Ok(())
+    }
+
+    /// Fails on the first flag given on the command line or read from the
+    /// environment collecting fewer or more values than it allows.
+    fn check_value_counts(&self) -> Result<(), Error> {
+        for node in &self.graph.nodes {
+            let flag = match &node.data.kind {
+                ArgType::Flag(flag)
+                    if node.data.found && node.data.source != Source::Default && flag.multiple =>
+                {
+                    flag
+                }
+                _ => continue,
+            };
+            let given = node.data.values.len();
+            if given < flag.min_values {
+                return Err(Error::TooFewValues {
+                    flag: format!("--{}", flag.long),
+                    min: flag.min_values,
+                    given,
+                });
+            }
+            if let Some(max) = flag.max_values.filter(|&max| given > max) {
+                return Err(Error::TooManyValues {
+                    flag: format!("--{}", flag.long),
+                    max,
+                    given,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a node built from the command line at the current level.
+    fn record(&mut self, kind: ArgType<'a>) -> Result<NodeIndex, Error> {
+        let index = self.add_node(Arg::new(kind))?;
+        self.graph.nodes[index.0].data.found = true;
+        Ok(index)
+    }
+
+    /// Adds a node at the current subcommand level, or as a root when no
+    /// subcommand has been matched yet.
+    fn add_node(&mut self, data: Arg<'a>) -> Result<NodeIndex, Error> {
+        match self.current_subcmd {
+            Some(index) => Ok(self.graph.add_node_to(index, data)?),
+            None => Ok(self.graph.add_node(data)),
+        }
+    }
+
+    /// Handles a flag matching nothing at the current level according to
+    /// the parser mode. In `Mode::Positional` it fills declared positionals
+    /// like any other argument.
+    fn unknown(&mut self, arg: OsString, error: Error) -> Result<(), Error> {
+        match self.mode {
+            Mode::Strict => Err(error),
+            Mode::Lenient => {
+                self.record(ArgType::UnknownFlag(arg))?;
+                Ok(())
+            }
+            Mode::Positional => self.handle_argument(arg, false),
+        }
+    }
+
+    /// The subcommand name or alias of the current level closest to `given`.
+    fn suggest_subcommand(&self, given: &str) -> Result<Option<String>, Error> {
+        let subcommands = help::subcommands(&self.graph, self.current_subcmd)?;
+        let candidates = subcommands.iter().flat_map(|(_, subcommand)| {
+            let aliases = subcommand.aliases.iter().map(|alias| &**alias);
+            Some(&*subcommand.name).into_iter().chain(aliases)
+        });
+        Ok(suggest::closest(given, candidates).map(str::to_string))
+    }
+
+    /// The long flag accepted at the current level closest to `--name`.
+    fn suggest_flag(&self, name: &str) -> Result<Option<String>, Error> {
+        let flags = help::flags(&self.graph, self.current_subcmd)?;
+        let candidates = flags.iter().map(|(flag, _)| &*flag.long);
+        Ok(suggest::closest(name, candidates).map(|long| format!("--{}", long)))
+    }
+
+    fn has_subcommands(&self) -> Result<bool, Error> {
+        let mut children = self.graph.successors(self.current_subcmd)?;
+        Ok(children
+            .any(|index| matches!(self.graph.nodes[index.0].data.kind, ArgType::SubCommand(_))))
+    }
+
+    fn handle_subcommand(&mut self, arg: &OsStr) -> Result<bool, Error> {
+        let arg = match arg.to_str() {
+            Some(arg) => arg,
+            None => return Ok(false),
+        };
+        let mut children = self.graph.successors(self.current_subcmd)?;
+        let result = children.find(|index| {
+            if let ArgType::SubCommand(subcommand) = &self.graph.nodes[index.0].data.kind {
+                if subcommand.name == arg {
+                    return true;
+                }
+                if subcommand.aliases.iter().any(|alias| alias == arg) {
+                    return true;
+                }
+            }
+            false
+        });
+        if let Some(index) = result {
+            self.graph.nodes[index.0].data.found = true;
+            self.current_subcmd = Some(index);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+impl<'a> Default for Parser<'a> {
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+fn iterate_subcommand_config<'a>(
+    graph: &mut Graph<Arg<'a>>,
+    current_subcmd: &SubCommandConfig<'a>,
+    previous_index: Option<NodeIndex>,
+) -> Result<(), Error> {
+    validate_name(&current_subcmd.name)?;
+    let subcmd_index;
+    let data = Arg::new(ArgType::SubCommand(SubCommand::from(current_subcmd)));
+    if let Some(index) = previous_index {
+        subcmd_index = graph.add_node_to(index, data)?;
+    } else {
+        subcmd_index = graph.add_node(data);
+    }
+    for flag in &current_subcmd.flags {
+        graph.add_node_to(subcmd_index, Arg::new(ArgType::Flag(flag.clone())))?;
+    }
+    check_positionals(&current_subcmd.positionals)?;
+    for positional in &current_subcmd.positionals {
+        graph.add_node_to(
+            subcmd_index,
+            Arg::new(ArgType::Positional(positional.clone())),
+        )?;
+    }
+    for rule in &current_subcmd.rules {
+        graph.add_node_to(subcmd_index, Arg::new(ArgType::Rule(rule.clone())))?;
+    }
+    let subcommands = &current_subcmd.subcommands;
+    for (i, subcommand) in subcommands.iter().enumerate() {
+        check_duplicate(&subcommands[..i], subcommand, Some(&current_subcmd.name))?;
+        iterate_subcommand_config(graph, subcommand, Some(subcmd_index))?;
+    }
+    Ok(())
+}
+
+/// The file name of a binary path, for display.
+fn file_name(bin: &OsStr) -> String {
+    let bin = Path::new(bin);
+    bin.file_name()
+        .unwrap_or(bin.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The part of `arg` starting at byte `start`, which must directly follow an
+/// ASCII character or a valid UTF-8 sequence.
+fn os_suffix(arg: &OsStr, start: usize) -> &OsStr {
+    // SAFETY: splitting right after valid UTF-8 is allowed by
+    // `from_encoded_bytes_unchecked`
+    unsafe { OsStr::from_encoded_bytes_unchecked(&arg.as_encoded_bytes()[start..]) }
+}
+
+/// Fails on a rule naming no flag or positional of its level nor any global
+/// flag declared above it, whatever the command line.
+fn check_rule_names(graph: &Graph<Arg>) -> Result<(), Error> {
+    for level in help::levels(graph)? {
+        for index in graph.ordered_successors(level.index)? {
+            let rule = match &graph.nodes[index.0].data.kind {
+                ArgType::Rule(rule) => rule,
+                _ => continue,
+            };
+            for name in rule.names() {
+                let flag = level.flags.iter().any(|(flag, _)| flag.name == name);
+                let positional = level.positionals.iter().any(|p| p.name == name);
+                if !flag && !positional {
+                    return Err(Error::InvalidRule(name.to_string()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails on a default value outside of the possible values of its flag.
+fn check_defaults(graph: &Graph<Arg>) -> Result<(), Error> {
+    for node in &graph.nodes {
+        if let ArgType::Flag(flag) = &node.data.kind {
+            let default = match &flag.default {
+                Some(default) => default,
+                None => continue,
+            };
+            if flag.split_value(OsString::from(&**default)).is_err() {
+                return Err(Error::InvalidDefault {
+                    flag: format!("--{}", flag.long),
+                    value: default.to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Positionals are matched in order, so a required one cannot follow an
+/// optional one and a variadic one has to be the last.
+fn check_positionals(positionals: &[Positional]) -> Result<(), Error> {
+    for pair in positionals.windows(2) {
+        if pair[0].variadic || (pair[1].required && !pair[0].required) {
+            return Err(Error::InvalidPositional(pair[1].name.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// The environment variable a flag declared under the subcommand `path` is
+/// read from, if any.
+fn env_var<S: AsRef<str>>(flag: &Flag, prefix: Option<&str>, path: &[S]) -> Option<String> {
+    if let Some(name) = &flag.env {
+        return Some(name.to_string());
+    }
+    let prefix = prefix.filter(|_| flag.takes_arg)?;
+    let mut parts = vec![prefix];
+    parts.extend(path.iter().map(AsRef::as_ref));
+    parts.push(&flag.long);
+    Some(parts.join("_").to_uppercase().replace('-', "_"))
+}
+
+/// Whether the environment value of a flag taking no value sets it.
+fn env_bool(flag: &Flag, value: &OsStr) -> Result<bool, Error> {
+    let lower = value.to_str().map(str::to_ascii_lowercase);
+    match lower.as_deref() {
+        Some("1" | "true" | "yes" | "on") => Ok(true),
+        Some("0" | "false" | "no" | "off") => Ok(false),
+        _ => Err(Error::InvalidValue {
+            arg: format!("--{}", flag.long),
+            value: value.to_string_lossy().into_owned(),
+            reason: "expected a boolean like `true` or `0`".to_string(),
+        }),
+    }
+}
+
+/// Splits `value` on `delimiter`, keeping empty parts.
+fn os_split(value: &OsStr, delimiter: char) -> Vec<OsString> {
+    let mut buffer = [0; 4];
+    let delimiter = delimiter.encode_utf8(&mut buffer).as_bytes();
+    let bytes = value.as_encoded_bytes();
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i + delimiter.len() <= bytes.len() {
+        if &bytes[i..i + delimiter.len()] == delimiter {
+            parts.push(&bytes[start..i]);
+            i += delimiter.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    parts.push(&bytes[start..]);
+    parts
+        .into_iter()
+        // SAFETY: every part is bounded by the ends of `value` or by the
+        // valid UTF-8 of the delimiter, as `from_encoded_bytes_unchecked`
+        // allows
+        .map(|part| unsafe { OsStr::from_encoded_bytes_unchecked(part) }.to_os_string())
+        .collect()
+}
+
+fn validate_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || Regex::new(r"\W").unwrap().is_match(name) {
+        return Err(Error::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Fails when a name or alias of `subcommand` is already a name or alias of
+/// one of its siblings.
+fn check_duplicate(
+    siblings: &[SubCommandConfig],
+    subcommand: &SubCommandConfig,
+    parent: Option<&str>,
+) -> Result<(), Error> {
+    let taken = |name: &str| {
+        siblings
+            .iter()
+            .any(|subcmd| subcmd.name == name || subcmd.aliases.iter().any(|alias| alias == name))
+    };
+    let mut names = Some(&subcommand.name)
+        .into_iter()
+        .chain(&subcommand.aliases);
+    if let Some(name) = names.find(|name| taken(name)) {
+        return Err(Error::DuplicateSubCommand {
+            name: name.to_string(),
+            parent: parent.map(String::from),
+        });
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct SubCommandConfig<'a> {
+    flags: Vec<Flag<'a>>,
+    name: Cow<'a, str>,
+    aliases: Vec<Cow<'a, str>>,
+    positionals: Vec<Positional<'a>>,
+    rules: Vec<Rule<'a>>,
+    subcommands: Vec<SubCommandConfig<'a>>,
+    description: Option<Cow<'a, str>>,
+    version: Option<Cow<'a, str>>,
+}
+
+impl<'a> SubCommandConfig<'a> {
+    /// Creates a subcommand. An invalid name is reported when tapping, see
+    /// `try_with_name` to catch it right away.
+    pub fn with_name(name: impl Into<Cow<'a, str>>) -> Self {
+        SubCommandConfig {
+            flags: vec![],
+            name: name.into(),
+            positionals: vec![],
+            rules: vec![],
+            subcommands: vec![],
+            aliases: vec![],
+            description: None,
+            version: None,
+        }
+    }
+
+    pub fn try_with_name(name: impl Into<Cow<'a, str>>) -> Result<Self, Error> {
+        let config = SubCommandConfig::with_name(name);
+        validate_name(&config.name)?;
+        Ok(config)
+    }
+
+    pub fn alias(mut self, alias: impl Into<Cow<'a, str>>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<Cow<'a, str>>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Overrides the program version for this subcommand and the ones below.
+    pub fn with_version(mut self, version: impl Into<Cow<'a, str>>) -> Self {
+        self.version = non_empty(version);
+        self
+    }
+
+    pub fn flag(
+        mut self,
+        name: impl Into<Cow<'a, str>>,
+        short: char,
+        long: impl Into<Cow<'a, str>>,
+        takes_arg: bool,
+    ) -> Self {
+        self.flags.push(Flag::new(name, short, long, takes_arg));
+        self
+    }
+
+    pub fn add_flag(mut self, flag: Flag<'a>) -> Self {
+        self.flags.push(flag);
+        self
+    }
+
+    pub fn positional(mut self, name: impl Into<Cow<'a, str>>, required: bool) -> Self {
+        self.positionals.push(Positional::new(name, required));
+        self
+    }
+
+    pub fn add_positional(mut self, positional: Positional<'a>) -> Self {
+        self.positionals.push(positional);
+        self
+    }
+
+    pub fn rule(mut self, rule: Rule<'a>) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn help(mut self) -> Self {
+        self.flags.push(Flag::help());
+        self
+    }
+
+    pub fn verbose(mut self) -> Self {
+        self.flags.push(Flag::verbose());
+        self
+    }
+
+    pub fn version(mut self) -> Self {
+        self.flags.push(Flag::version());
+        self
+    }
+
+    pub fn debug(mut self) -> Self {
+        self.flags.push(Flag::debug());
+        self
+    }
+
+    pub fn subcommand(mut self, subcommand: SubCommandConfig<'a>) -> Self {
+        self.subcommands.push(subcommand);
+        self
+    }
+
+    pub fn try_subcommand(self, subcommand: SubCommandConfig<'a>) -> Result<Self, Error> {
+        check_duplicate(&self.subcommands, &subcommand, Some(&self.name))?;
+        Ok(self.subcommand(subcommand))
+    }
+}
+
+impl<'a> From<&SubCommandConfig<'a>> for SubCommand<'a> {
+    fn from(subcmd: &SubCommandConfig<'a>) -> Self {
+        SubCommand {
+            name: subcmd.name.clone(),
+            aliases: subcmd.aliases.clone(),
+            description: subcmd.description.clone(),
+            version: subcmd.version.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
     fn parser_new() {
         let mut parser = Parser::new();
-        // assert_eq!(parser.binary_flags.len(), 0);
         parser.help();
-        // assert_eq!(parser.binary_flags.len(), 1);
-        // parser.license();
-        // assert_eq!(parser.binary_flags.len(), 2);
-        // parser.subcommand(SubCommandConfig::with_name("test").help().verbose());
         parser.subcommand(
             SubCommandConfig::with_name("binary_subcmd")
                 .verbose()
                 .alias("bin")
                 .subcommand(SubCommandConfig::with_name("subsubcmd").debug()),
         );
-        let args = vec!["tap", "test", "-hvd", "--help"];
-        // parser.tap(args);
-        // let args = vec!["tap", "test", "-hvd", "--help"];
-        // let parsed = parser.tap_from(args);
-        // assert_eq!(parsed.args.len(), 1);
-        // assert_eq!(parsed.options.len(), 2);
-        // assert_eq!(parsed.scraps.len(), 2);
-    }
-}
-// pub struct Parsed {}
-//
-// impl<'a> Parser<'a> {
-// pub fn new() -> Parser<'a> {
-// let mut graph = Graph::<ArgType>::new();
-// graph.add_node(ArgType::Root);
-// let levels = vec![];
-// levels.push(Level::newBinary());
-// Parser {
-// graph,
-// levels,
-// current_level: Some(NodeIndex(0)),
-// }
-// }
-//
-// pub fn flag(
-// &mut self,
-// name: &'a str,
-// short: char,
-// long: &'a str,
-// takes_value: bool,
-// ) -> &mut Parser<'a> {
-// self.flags.push(Flag(name, short, long, takes_value));
-// self
-// }
-//
-// pub fn help(&mut self) -> &mut Parser<'a> {
-// self.flags.push(Flag("help", 'h', "help", false));
-// self
-// }
-//
-// pub fn verbose(&mut self) -> &mut Parser<'a> {
-// self.flags.push(Flag("verbose", 'V', "verbose", false));
-// self
-// }
-//
-// pub fn version(&mut self) -> &mut Parser<'a> {
-// self.flags.push(Flag("version", 'v', "version", false));
-// self
-// }
-//
-// pub fn license(&mut self) -> &mut Parser<'a> {
-// self.flags.push(Flag("license", 'L', "license", false));
-// self
-// }
-//
-// pub fn debug(&mut self) -> &mut Parser<'a> {
-// self.flags.push(Flag("debug", 'd', "debug", false));
-// self
-// }
-//
-// pub fn tap(&mut self) -> Parsed {
-// let mut args = env::args();
-// args.next();
-// // self.logic(args)
-// let mut parsed = Parsed {};
-// parsed
-// }
-//
-// pub fn tap_from(&mut self, mut args: Vec<&str>) -> Parsed {
-// let mut iter = args.iter().map(|arg| arg.to_string());
-// iter.next();
-// // self.logic(iter)
-// let mut parsed = Parsed {};
-// parsed
-// }
-
-// fn logic<A: Iterator<Item = String>>(&mut self, mut args: A) -> Parsed {
-// let mut parsed = Parsed {};
-// let mut tokens = self.tokenize(&mut args);
-// parsed
-// }
-//
-// fn tokenize<A: Iterator<Item = String>>(&mut self, mut args: A) {
-// let mut accept_opt = true;
-// while let Some(arg) = args.next() {
-// if arg == "-" {
-// // tokens.push(Token::Argument(String::from("-")));
-// self.graph.add_node(ArgType::Argument(arg));
-// } else if arg == "--" {
-// self.graph.add_node(ArgType::Over);
-// accept_opt = false;
-// } else if arg.len() > 2 && arg.starts_with("--") && accept_opt == true {
-// self.parse_long_option(&arg);
-// } else if arg.len() > 1 && arg.starts_with("-") && accept_opt == true {
-// self.parse_option(&arg);
-// } else {
-// // tokens.push(Token::Argument(String::from(arg)));
-// }
-// }
-// }
-
-// fn normalize<'a>(&mut self, tokens: &mut Vec<Token<'a>>) {
-// let mut to_merge = vec![];
-// let mut inc = 0;
-// let mut token_iter = tokens.iter().enumerate().peekable();
-// while let Some((i, token)) = token_iter.next() {
-// if let Token::Option(flag, arg) = token {
-// if flag.3 == true && *arg == None {
-// if let Some((_j, Token::Argument(value))) = token_iter.peek() {
-// to_merge.push((i - inc, *flag, value.to_string()));
-// inc += 1;
-// }
-// }
-// }
-// }
-// for (i, flag, arg) in to_merge {
-// tokens.remove(i);
-// tokens.remove(i);
-// tokens.insert(i, Token::Option(flag, Some(arg)));
-// }
-// }
-
-// fn parse_option(&mut self, arg: &str) {
-// let current_arg = &arg[1..];
-// for (i, c) in current_arg.char_indices() {
-// if let Some(option) = self.flags.iter().find(|option| c == option.1) {
-// if option.3 == true {
-// if i + 1 < current_arg.len() {
-// let arg_opt = &current_arg[i + 1..];
-// // tokens.push(Token::Option(&option, Some(String::from(arg_opt))));
-// break;
-// } else {
-// // tokens.push(Token::Option(&option, None));
-// }
-// } else {
-// // tokens.push(Token::Option(&option, None));
-// }
-// } else {
-// // tokens.push(Token::UnknownOpt(c.to_string()));
-// }
-// }
-// }
-
-// fn parse_long_option(&mut self, arg: &str) {
-// let current_arg = &arg[2..];
-// match current_arg.find("=") {
-// None => {
-// if let Some(option) = self.flags.iter().find(|option| current_arg == option.2) {
-// tokens.push(Token::Option(&option, None));
-// } else {
-// tokens.push(Token::UnknownOpt(current_arg.to_string()));
-// }
-// }
-// Some(i) => {
-// let first = &current_arg[..i];
-// let last = &current_arg[i + 1..];
-// if let Some(option) = self.flags.iter().find(|option| first == option.2) {
-// if option.3 == true && !last.is_empty() {
-// tokens.push(Token::Option(&option, Some(String::from(last))));
-// } else {
-// tokens.push(Token::Option(&option, None));
-// }
-// } else {
-// tokens.push(Token::UnknownOpt(current_arg.to_string()));
-// }
-// }
-// }
-// }
-// }
-
-// #[derive(Debug)]
-// enum Token<'a> {
-// Argument(String),
-// Option(&'a Flag, Option<String>),
-// UnknownOpt(String),
-// }
-
-// #[derive(Debug)]
-// pub struct Opt<'a> {
-// flag: &'a Flag,
-// arg: Option<String>,
-// }
-
-// impl<'a> Opt<'a> {
-// fn new(flag: &'a Flag, arg: Option<String>) -> Opt {
-// Opt { flag, arg }
-// }
-// }
-
-// #[derive(Debug)]
-// pub struct Parsed<'a> {
-// options: Vec<Opt<'a>>,
-// args: Vec<String>,
-// scraps: Vec<String>,
-// }
+        let parsed = parser.tap(vec!["test", "bin", "subsubcmd", "file"]);
+        assert_eq!(parsed.subcommands(), &["binary_subcmd", "subsubcmd"]);
+        assert_eq!(parsed.subcommand(), Some("subsubcmd"));
+        assert_eq!(parsed.args(), &["test", "file"]);
+        assert_eq!(parsed.flags().len(), 0);
+    }
+
+    #[test]
+    fn tap_twice() {
+        let mut parser = Parser::new();
+        parser.subcommand(SubCommandConfig::with_name("sub"));
+        let parsed = parser.tap(vec!["sub", "one"]);
+        assert_eq!(parsed.subcommands(), &["sub"]);
+        assert_eq!(parsed.args(), &["one"]);
+        let parsed = parser.tap(vec!["two"]);
+        assert_eq!(parsed.subcommands().len(), 0);
+        assert_eq!(parsed.args(), &["two"]);
+    }
+
+    #[test]
+    fn short_flag_clusters() {
+        let mut parser = Parser::new();
+        parser
+            .help()
+            .verbose()
+            .debug()
+            .flag("output", 'o', "output", true);
+        let parsed = parser.tap(vec!["-Vd", "-ofile.txt", "arg"]);
+        assert!(!parsed.is_present("help"));
+        assert!(parsed.is_present("verbose"));
+        assert!(parsed.is_present("debug"));
+        assert_eq!(parsed.value_of("output").as_deref(), Some("file.txt"));
+        assert_eq!(parsed.args(), &["arg"]);
+        let parsed = parser.tap(vec!["-do", "file.txt", "arg"]);
+        assert!(parsed.is_present("debug"));
+        assert_eq!(parsed.value_of("output").as_deref(), Some("file.txt"));
+        assert_eq!(parsed.args(), &["arg"]);
+        let parsed = parser.tap(vec!["-Vod"]);
+        assert!(!parsed.is_present("debug"));
+        assert_eq!(parsed.value_of("output").as_deref(), Some("d"));
+        let error = parser.try_tap(vec!["-dhV"]).unwrap_err();
+        assert!(matches!(error, Error::Help(_)));
+    }
+
+    #[test]
+    fn short_flags_at_subcommand_level() {
+        let mut parser = Parser::new();
+        parser
+            .help()
+            .subcommand(SubCommandConfig::with_name("sub").debug());
+        let parsed = parser.tap(vec!["-d", "sub", "-hd"]);
+        assert_eq!(parsed.subcommands(), &["sub"]);
+        assert!(!parsed.is_present("help"));
+        assert_eq!(parsed.flag("debug").map(|opt| opt.level()), Some(1));
+        assert_eq!(parsed.unknown(), &["-d", "-h"]);
+    }
+
+    #[test]
+    fn unknown_modes() {
+        let mut parser = Parser::new();
+        parser
+            .verbose()
+            .subcommand(SubCommandConfig::with_name("sub"));
+        let args = vec!["-Vx", "--nope=1", "typo", "sub", "file"];
+        let parsed = parser.tap(args.clone());
+        assert!(parsed.is_present("verbose"));
+        assert_eq!(parsed.unknown(), &["-x", "--nope=1"]);
+        assert_eq!(parsed.args(), &["typo", "file"]);
+        parser.mode(Mode::Positional);
+        let parsed = parser.tap(args.clone());
+        assert_eq!(parsed.unknown().len(), 0);
+        assert_eq!(parsed.args(), &["-Vx", "--nope=1", "typo", "file"]);
+        parser.mode(Mode::Strict);
+        assert_eq!(
+            parser.try_tap(args).unwrap_err(),
+            Error::UnknownFlag("-x".to_string())
+        );
+        assert_eq!(
+            parser.try_tap(vec!["--nope=1"]).unwrap_err(),
+            Error::UnknownFlag("--nope".to_string())
+        );
+        assert_eq!(
+            parser.try_tap(vec!["typo"]).unwrap_err(),
+            Error::UnknownArgument("typo".to_string())
+        );
+        assert!(parser.try_tap(vec!["sub", "file"]).is_ok());
+    }
+
+    #[test]
+    fn long_flags() {
+        let mut parser = Parser::new();
+        parser
+            .verbose()
+            .flag("output", 'o', "output", true)
+            .subcommand(SubCommandConfig::with_name("sub").flag("level", 'l', "level", true));
+        let parsed = parser.tap(vec!["--verbose", "--output=out.txt", "sub", "--level", "3"]);
+        assert!(parsed.is_present("verbose"));
+        assert_eq!(parsed.value_of("output").as_deref(), Some("out.txt"));
+        assert_eq!(parsed.value_of("level").as_deref(), Some("3"));
+        assert_eq!(parsed.args().len(), 0);
+        let parsed = parser.tap(vec!["--output", "--verbose"]);
+        assert!(!parsed.is_present("verbose"));
+        assert_eq!(parsed.value_of("output").as_deref(), Some("--verbose"));
+        let parsed = parser.tap(vec!["--level=3"]);
+        assert!(!parsed.is_present("level"));
+    }
+
+    #[test]
+    fn terminator_and_stdin() {
+        let mut parser = Parser::new();
+        parser
+            .verbose()
+            .subcommand(SubCommandConfig::with_name("sub"));
+        let parsed = parser.tap(vec!["-", "--verbose", "--", "-V", "sub", "--", "-"]);
+        assert!(parsed.is_present("verbose"));
+        assert_eq!(parsed.subcommands().len(), 0);
+        assert_eq!(parsed.args(), &["-", "-V", "sub", "--", "-"]);
+        assert_eq!(parsed.terminator(), Some(1));
+        assert_eq!(parsed.trailing(), &["-V", "sub", "--", "-"]);
+        let parsed = parser.tap(vec!["sub", "file", "--"]);
+        assert_eq!(parsed.subcommands(), &["sub"]);
+        assert_eq!(parsed.terminator(), Some(1));
+        assert_eq!(parsed.trailing().len(), 0);
+        let parsed = parser.tap(vec!["file"]);
+        assert_eq!(parsed.terminator(), None);
+        assert_eq!(parsed.trailing().len(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_arguments() {
+        use std::os::unix::ffi::OsStrExt;
+        let raw = |bytes: &[u8]| OsStr::from_bytes(bytes).to_os_string();
+        let mut parser = Parser::new();
+        parser
+            .verbose()
+            .flag("output", 'o', "output", true)
+            .subcommand(SubCommandConfig::with_name("sub"));
+        let args = vec![
+            raw(b"-Vo\xff.txt"),
+            raw(b"--output=\xfe"),
+            raw(b"-\xff"),
+            raw(b"sub"),
+            raw(b"file\xff"),
+        ];
+        let parsed = parser.tap_os(args);
+        assert!(parsed.is_present("verbose"));
+        assert_eq!(parsed.value_of_os("output"), Some(raw(b"\xfe").as_os_str()));
+        assert_eq!(parsed.subcommands(), &["sub"]);
+        assert_eq!(parsed.args_os(), &[raw(b"file\xff")]);
+        assert_eq!(parsed.args(), &["file\u{fffd}"]);
+        assert_eq!(parsed.unknown_os(), &[raw(b"-\xff")]);
+        let parsed = parser.tap_os(vec![raw(b"-o\xff.txt")]);
+        assert_eq!(parsed.value_of("output").as_deref(), Some("\u{fffd}.txt"));
+    }
+
+    #[test]
+    fn tap_env() {
+        let mut parser = Parser::new();
+        let argv = vec!["/usr/bin/app", "x"].into_iter().map(OsString::from);
+        let parsed = parser.try_tap_argv(argv).unwrap();
+        assert_eq!(parsed.bin(), Some(OsStr::new("/usr/bin/app")));
+        assert_eq!(parsed.bin_name().as_deref(), Some("app"));
+        assert_eq!(parsed.args(), &["x"]);
+        assert_eq!(parser.tap(vec![]).bin(), None);
+    }
+
+    #[test]
+    fn global_flags() {
+        let mut parser = Parser::new();
+        parser
+            .add_flag(Flag::new("verbose", 'V', "verbose", false).global())
+            .flag("local", 'l', "local", false)
+            .subcommand(
+                SubCommandConfig::with_name("sub")
+                    .add_flag(Flag::new("config", 'c', "config", true).global())
+                    .subcommand(SubCommandConfig::with_name("deep")),
+            );
+        let parsed = parser.tap(vec!["sub", "deep", "-Vc", "file", "--verbose", "-l"]);
+        assert_eq!(parsed.subcommands(), &["sub", "deep"]);
+        assert_eq!(parsed.flag("verbose").map(Opt::level), Some(2));
+        assert_eq!(parsed.flag("config").map(Opt::level), Some(2));
+        assert_eq!(parsed.value_of("config").as_deref(), Some("file"));
+        assert!(!parsed.is_present("local"));
+        assert_eq!(parsed.unknown(), &["-l"]);
+        let parsed = parser.tap(vec!["-V", "sub", "-V"]);
+        let levels: Vec<usize> = parsed.flags().iter().map(Opt::level).collect();
+        assert_eq!(levels, vec![0, 1]);
+        let parsed = parser.tap(vec!["--config", "file"]);
+        assert!(!parsed.is_present("config"));
+    }
+
+    #[test]
+    fn help() {
+        let mut parser = Parser::new();
+        parser
+            .description("a test binary")
+            .help()
+            .add_flag(
+                Flag::new("config", 'c', "config", true)
+                    .global()
+                    .description("Use this config file"),
+            )
+            .subcommand(
+                SubCommandConfig::with_name("remote")
+                    .description("Manage remotes")
+                    .alias("r")
+                    .alias("rem")
+                    .help()
+                    .subcommand(SubCommandConfig::with_name("add")),
+            )
+            .subcommand(SubCommandConfig::with_name("init"));
+        parser.bin = Some(OsString::from("/usr/bin/app"));
+        let help = parser.render_help(&[]).unwrap();
+        assert_eq!(
+            help,
+            "Usage: app [OPTIONS] [COMMAND]\n\
+             \n\
+             a test binary\n\
+             \n\
+             Options:\n\
+             \x20 -h, --help             Print help\n\
+             \x20 -c, --config <CONFIG>  Use this config file\n\
+             \n\
+             Commands:\n\
+             \x20 remote  Manage remotes [aliases: r, rem]\n\
+             \x20 init\n"
+        );
+        let help = parser.render_help(&["r", "add"]).unwrap();
+        assert_eq!(
+            help,
+            "Usage: app remote add [OPTIONS]\n\
+             \n\
+             Options:\n\
+             \x20 -c, --config <CONFIG>  Use this config file\n"
+        );
+        assert_eq!(
+            parser.render_help(&["nope"]).unwrap_err(),
+            Error::UnknownArgument("nope".to_string())
+        );
+        let help = parser.render_help(&["remote"]).unwrap();
+        assert_eq!(
+            parser
+                .try_tap(vec!["remote", "-h", "--unknown"])
+                .unwrap_err(),
+            Error::Help(help)
+        );
+        let help = parser.render_help(&[]).unwrap();
+        assert_eq!(
+            parser.try_tap(vec!["--help", "remote"]).unwrap_err(),
+            Error::Help(help)
+        );
+    }
+
+    #[test]
+    fn positionals() {
+        let mut parser = Parser::new();
+        parser
+            .mode(Mode::Strict)
+            .help()
+            .positional("input", true)
+            .positional("output", false)
+            .subcommand(
+                SubCommandConfig::with_name("add")
+                    .add_positional(Positional::new("name", true).description("Remote name"))
+                    .add_positional(Positional::new("urls", false).variadic()),
+            );
+        let parsed = parser.try_tap(vec!["in.txt"]).unwrap();
+        assert_eq!(
+            parsed.positional("input").unwrap().value().as_deref(),
+            Some("in.txt")
+        );
+        assert!(parsed.positional("output").is_none());
+        let parsed = parser
+            .try_tap(vec!["in.txt", "out.txt", "add", "origin", "a", "b"])
+            .unwrap();
+        assert_eq!(
+            parsed.positional("output").unwrap().value().as_deref(),
+            Some("out.txt")
+        );
+        let urls = parsed.positional("urls").unwrap();
+        assert_eq!(urls.values(), &["a", "b"]);
+        assert_eq!(urls.level(), 1);
+        assert_eq!(parsed.args(), &["in.txt", "out.txt", "origin", "a", "b"]);
+        assert_eq!(
+            parser.try_tap(vec![]).unwrap_err(),
+            Error::MissingPositional("input".to_string())
+        );
+        assert_eq!(
+            parser.try_tap(vec!["in.txt", "add"]).unwrap_err(),
+            Error::MissingPositional("name".to_string())
+        );
+        assert_eq!(
+            parser
+                .try_tap(vec!["in.txt", "out.txt", "extra"])
+                .unwrap_err(),
+            Error::UnknownArgument("extra".to_string())
+        );
+        for mode in [Mode::Lenient, Mode::Positional] {
+            parser.mode(mode);
+            assert_eq!(
+                parser
+                    .try_tap(vec!["in.txt", "out.txt", "extra"])
+                    .unwrap_err(),
+                Error::UnknownArgument("extra".to_string())
+            );
+        }
+        let parsed = parser.try_tap(vec!["-x", "-y"]).unwrap();
+        assert_eq!(
+            parsed.positional("output").unwrap().value().as_deref(),
+            Some("-y")
+        );
+        assert_eq!(
+            parser.try_tap(vec!["-x", "-y", "-z"]).unwrap_err(),
+            Error::UnknownArgument("-z".to_string())
+        );
+        parser.mode(Mode::Strict);
+        let parsed = parser.try_tap(vec!["--", "-in", "-out", "-x"]).unwrap();
+        assert_eq!(
+            parsed.positional("input").unwrap().value().as_deref(),
+            Some("-in")
+        );
+        assert_eq!(parsed.trailing(), &["-in", "-out", "-x"]);
+        parser.bin = Some(OsString::from("app"));
+        let help = parser.render_help(&["add"]).unwrap();
+        assert_eq!(
+            help,
+            "Usage: app add <NAME> [URLS]...\n\
+             \n\
+             Arguments:\n\
+             \x20 <NAME>     Remote name\n\
+             \x20 [URLS]...\n"
+        );
+        parser.positional("late", true);
+        assert_eq!(
+            parser.try_tap(vec!["in.txt"]).unwrap_err(),
+            Error::InvalidPositional("late".to_string())
+        );
+    }
+
+    #[test]
+    fn typed_values() {
+        let mut parser = Parser::new();
+        parser
+            .flag("jobs", 'j', "jobs", true)
+            .flag("port", 'p', "port", true)
+            .add_positional(Positional::new("sizes", false).variadic());
+        let parsed = parser
+            .try_tap(vec!["-j", "4", "-p", "80", "--port=8080", "1", "2"])
+            .unwrap();
+        assert_eq!(parsed.value_as::<u8>("jobs"), Ok(Some(4)));
+        assert_eq!(parsed.values_as::<u16>("port"), Ok(vec![80, 8080]));
+        assert_eq!(parsed.value_as::<u16>("missing"), Ok(None));
+        assert_eq!(parsed.positional_values_as::<u64>("sizes"), Ok(vec![1, 2]));
+        let parsed = parser.try_tap(vec!["-j", "many", "1", "x"]).unwrap();
+        assert_eq!(
+            parsed.value_as::<u8>("jobs"),
+            Err(Error::InvalidValue {
+                arg: "--jobs".to_string(),
+                value: "many".to_string(),
+                reason: "invalid digit found in string".to_string(),
+            })
+        );
+        let error = parsed.positional_values_as::<u64>("sizes").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value `x` for `[SIZES]...`: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn occurrences() {
+        let mut parser = Parser::new();
+        parser
+            .verbose()
+            .add_flag(Flag::new("output", 'o', "output", true).repeat(Repeat::Reject))
+            .add_flag(Flag::new("color", 'c', "color", true).repeat(Repeat::LastWins))
+            .subcommand(SubCommandConfig::with_name("sub").verbose());
+        let parsed = parser
+            .try_tap(vec!["-VVV", "--verbose", "sub", "-V"])
+            .unwrap();
+        assert_eq!(parsed.flags()[0].occurrences(), 4);
+        assert_eq!(parsed.flag("verbose").unwrap().occurrences(), 1);
+        assert_eq!(parsed.verbosity(), 5);
+        let parsed = parser.try_tap(vec!["-c", "red", "--color=blue"]).unwrap();
+        assert_eq!(parsed.flag("color").unwrap().values(), &["blue"]);
+        assert_eq!(parsed.occurrences("color"), 2);
+        assert_eq!(
+            parser.try_tap(vec!["-o", "a", "-oa"]).unwrap_err(),
+            Error::RepeatedFlag("--output".to_string())
+        );
+        assert_eq!(parser.try_tap(vec![]).unwrap().verbosity(), 0);
+    }
+
+    #[test]
+    fn multiple_values() {
+        let mut parser = Parser::new();
+        parser
+            .add_flag(Flag::new("include", 'i', "include", false).multiple())
+            .add_flag(
+                Flag::new("features", 'F', "features", true)
+                    .delimiter(',')
+                    .min_values(2)
+                    .max_values(3),
+            );
+        let parsed = parser
+            .try_tap(vec!["-i", "a", "--include=b", "-F", "x,y", "-ic"])
+            .unwrap();
+        assert_eq!(parsed.flag("include").unwrap().values(), &["a", "b", "c"]);
+        assert_eq!(parsed.flag("features").unwrap().values(), &["x", "y"]);
+        assert_eq!(
+            parser.try_tap(vec!["--features", "x"]).unwrap_err(),
+            Error::TooFewValues {
+                flag: "--features".to_string(),
+                min: 2,
+                given: 1
+            }
+        );
+        assert_eq!(
+            parser.try_tap(vec!["-F", "x,y", "-F", "z,"]).unwrap_err(),
+            Error::TooManyValues {
+                flag: "--features".to_string(),
+                max: 3,
+                given: 4
+            }
+        );
+        parser.bin = Some(OsString::from("app"));
+        let help = parser.render_help(&[]).unwrap();
+        assert!(help.contains("  -i, --include <INCLUDE>...\n"));
+    }
+
+    #[test]
+    fn defaults_and_required() {
+        let mut parser = Parser::new();
+        parser
+            .add_flag(
+                Flag::new("color", 'c', "color", true)
+                    .default_value("auto")
+                    .description("Colorize output"),
+            )
+            .add_flag(Flag::new("token", 't', "token", true).global().required())
+            .subcommand(
+                SubCommandConfig::with_name("push")
+                    .add_flag(Flag::new("remote", 'r', "remote", true).required()),
+            );
+        let parsed = parser.try_tap(vec!["-t", "abc"]).unwrap();
+        let color = parsed.flag("color").unwrap();
+        assert_eq!(color.value().as_deref(), Some("auto"));
+        assert_eq!(color.source(), &Source::Default);
+        assert!(!parsed.is_present("color"));
+        let parsed = parser.try_tap(vec!["-t", "abc", "--color=never"]).unwrap();
+        assert_eq!(parsed.flag("color").unwrap().source(), &Source::CommandLine);
+        assert!(parsed.is_present("color"));
+        assert_eq!(
+            parser.try_tap(vec![]).unwrap_err(),
+            Error::MissingFlag("--token".to_string())
+        );
+        assert_eq!(
+            parser.try_tap(vec!["-t", "abc", "push"]).unwrap_err(),
+            Error::MissingFlag("--remote".to_string())
+        );
+        assert!(parser
+            .try_tap(vec!["push", "-r", "origin", "-t", "abc"])
+            .is_ok());
+        parser.bin = Some(OsString::from("app"));
+        let help = parser.render_help(&[]).unwrap();
+        assert!(help.contains("  -c, --color <COLOR>  Colorize output [default: auto]\n"));
+    }
+
+    #[test]
+    fn env_fallback() {
+        env::set_var("TAP_TEST_REMOTE_ADD_URL", "https://example.com");
+        env::set_var("TAP_TEST_TOKEN", "from-env");
+        env::set_var("TAP_TEST_QUIET", "1");
+        let mut parser = Parser::new();
+        parser
+            .env_prefix("tap_test")
+            .add_flag(Flag::new("token", 't', "token", true).global())
+            .add_flag(Flag::new("quiet", 'q', "quiet", false).env("TAP_TEST_QUIET"))
+            .add_flag(Flag::new("verbose", 'V', "verbose", false))
+            .subcommand(
+                SubCommandConfig::with_name("remote").subcommand(
+                    SubCommandConfig::with_name("add")
+                        .add_flag(Flag::new("url", 'u', "url", true).default_value("none")),
+                ),
+            );
+        let parsed = parser.try_tap(vec!["remote", "add"]).unwrap();
+        let url = parsed.flag("url").unwrap();
+        assert_eq!(url.value().as_deref(), Some("https://example.com"));
+        assert_eq!(
+            url.source(),
+            &Source::Env("TAP_TEST_REMOTE_ADD_URL".to_string())
+        );
+        assert_eq!(parsed.value_of("token").as_deref(), Some("from-env"));
+        assert!(parsed.is_present("quiet"));
+        assert!(!parsed.is_present("verbose"));
+        env::set_var("TAP_TEST_QUIET", "False");
+        let parsed = parser.try_tap(vec![]).unwrap();
+        assert!(!parsed.is_present("quiet"));
+        env::set_var("TAP_TEST_QUIET", "maybe");
+        assert!(matches!(
+            parser.try_tap(vec![]).unwrap_err(),
+            Error::InvalidValue { arg, .. } if arg == "--quiet"
+        ));
+        env::set_var("TAP_TEST_QUIET", "1");
+        let parsed = parser
+            .try_tap(vec!["remote", "add", "-u", "cli", "-t", "cli"])
+            .unwrap();
+        assert_eq!(parsed.value_of("url").as_deref(), Some("cli"));
+        assert_eq!(parsed.value_of("token").as_deref(), Some("cli"));
+        assert_eq!(parsed.flag("token").unwrap().source(), &Source::CommandLine);
+        parser.bin = Some(OsString::from("app"));
+        let help = parser.render_help(&["remote", "add"]).unwrap();
+        assert!(help.contains("--url <URL>      [default: none] [env: TAP_TEST_REMOTE_ADD_URL]\n"));
+        assert!(help.contains("--token <TOKEN>  [env: TAP_TEST_TOKEN]\n"));
+        env::set_var("TAP_TEST_INC", "a,b,c,d");
+        let mut parser = Parser::new();
+        parser.add_flag(
+            Flag::new("inc", 'i', "inc", true)
+                .delimiter(',')
+                .max_values(2)
+                .env("TAP_TEST_INC"),
+        );
+        assert_eq!(
+            parser.try_tap(vec![]).unwrap_err(),
+            Error::TooManyValues {
+                flag: "--inc".to_string(),
+                max: 2,
+                given: 4,
+            }
+        );
+        env::set_var("TAP_TEST_INC", "a,b");
+        let parsed = parser.try_tap(vec![]).unwrap();
+        assert_eq!(parsed.flag("inc").unwrap().values(), &["a", "b"]);
+    }
+
+    #[test]
+    fn rules() {
+        let mut parser = Parser::new();
+        parser
+            .flag("json", 'j', "json", false)
+            .add_flag(Flag::new("color", 'c', "color", true).default_value("auto"))
+            .flag("key", 'k', "key", true)
+            .flag("cert", 'C', "cert", true)
+            .rule(Rule::conflicts("json", "color"))
+            .rule(Rule::requires("key", "cert"))
+            .subcommand(
+                SubCommandConfig::with_name("fetch")
+                    .flag("all", 'a', "all", false)
+                    .positional("remote", false)
+                    .rule(Rule::exactly_one(["all", "remote"])),
+            );
+        assert!(parser.try_tap(vec!["--json"]).is_ok());
+        assert_eq!(
+            parser.try_tap(vec!["--json", "-c", "never"]).unwrap_err(),
+            Error::Conflict("--json".to_string(), "--color".to_string())
+        );
+        assert_eq!(
+            parser.try_tap(vec!["-k", "key.pem"]).unwrap_err(),
+            Error::MissingRequirement("--key".to_string(), "--cert".to_string())
+        );
+        assert!(parser
+            .try_tap(vec!["-k", "key.pem", "-C", "cert.pem"])
+            .is_ok());
+        assert!(parser.try_tap(vec!["fetch", "origin"]).is_ok());
+        assert_eq!(
+            parser.try_tap(vec!["fetch", "-a", "origin"]).unwrap_err(),
+            Error::Conflict("--all".to_string(), "[REMOTE]".to_string())
+        );
+        let error = parser.try_tap(vec!["fetch"]).unwrap_err();
+        assert_eq!(error.to_string(), "one of `--all`, `[REMOTE]` is required");
+        parser.rule(Rule::at_least_one(["json", "nope"]));
+        assert_eq!(
+            parser.try_tap(vec![]).unwrap_err(),
+            Error::InvalidRule("nope".to_string())
+        );
+        let mut parser = Parser::new();
+        parser
+            .add_flag(Flag::new("json", 'j', "json", false).global())
+            .subcommand(
+                SubCommandConfig::with_name("s")
+                    .flag("yaml", 'y', "yaml", false)
+                    .rule(Rule::conflicts("json", "yaml")),
+            );
+        for args in [vec!["-j", "s", "-y"], vec!["s", "-j", "-y"]] {
+            assert_eq!(
+                parser.try_tap(args).unwrap_err(),
+                Error::Conflict("--json".to_string(), "--yaml".to_string())
+            );
+        }
+        assert!(parser.try_tap(vec!["s", "-y"]).is_ok());
+        // checked against the spec even when the level is not reached
+        parser.subcommand(SubCommandConfig::with_name("t").rule(Rule::requires("json", "nope")));
+        assert_eq!(
+            parser.try_tap(vec!["s", "-y"]).unwrap_err(),
+            Error::InvalidRule("nope".to_string())
+        );
+    }
+
+    #[test]
+    fn possible_values() {
+        let mut parser = Parser::new();
+        parser
+            .add_flag(
+                Flag::new("format", 'f', "format", true)
+                    .possible_values(["json", "yaml", "text"])
+                    .description("Output format"),
+            )
+            .add_flag(
+                Flag::new("level", 'l', "level", true)
+                    .possible_values(["low", "high"])
+                    .delimiter(',')
+                    .ignore_case(),
+            );
+        let parsed = parser
+            .try_tap(vec!["-f", "yaml", "--level=LOW,High"])
+            .unwrap();
+        assert_eq!(parsed.value_of("format").as_deref(), Some("yaml"));
+        assert_eq!(parsed.flag("level").unwrap().values(), &["low", "high"]);
+        let error = parser.try_tap(vec!["--format", "JSON"]).unwrap_err();
+        assert_eq!(
+            error,
+            Error::InvalidChoice {
+                flag: "--format".to_string(),
+                value: "JSON".to_string(),
+                choices: vec!["json".to_string(), "yaml".to_string(), "text".to_string()],
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid value `JSON` for `--format`, possible values: json, yaml, text"
+        );
+        parser.bin = Some(OsString::from("app"));
+        let help = parser.render_help(&[]).unwrap();
+        assert!(help.contains("Output format [possible values: json, yaml, text]\n"));
+        let mut parser = Parser::new();
+        parser.add_flag(
+            Flag::new("format", 'f', "format", true)
+                .default_value("a")
+                .possible_values(["b", "c"]),
+        );
+        let error = parser.try_tap(vec!["-f", "b"]).unwrap_err();
+        assert_eq!(
+            error,
+            Error::InvalidDefault {
+                flag: "--format".to_string(),
+                value: "a".to_string(),
+            }
+        );
+        assert!(error.is_spec_error());
+        let mut parser = Parser::new();
+        parser.add_flag(
+            Flag::new("format", 'f', "format", true)
+                .default_value("B")
+                .possible_values(["b", "c"])
+                .ignore_case(),
+        );
+        let parsed = parser.try_tap(vec![]).unwrap();
+        assert_eq!(parsed.value_of("format").as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn suggestions() {
+        let mut parser = Parser::new();
+        parser
+            .mode(Mode::Strict)
+            .add_flag(Flag::new("verbose", 'V', "verbose", false).global())
+            .subcommand(SubCommandConfig::with_name("binary_subcmd"))
+            .subcommand(
+                SubCommandConfig::with_name("remote")
+                    .alias("rmt")
+                    .flag("force", 'f', "force", false),
+            );
+        let error = parser.try_tap(vec!["binary_subcm"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown argument `binary_subcm`, did you mean `binary_subcmd`?"
+        );
+        assert_eq!(
+            parser.try_tap(vec!["rmtt"]).unwrap_err(),
+            Error::Misspelled {
+                given: "rmtt".to_string(),
+                suggestion: "rmt".to_string()
+            }
+        );
+        let error = parser.try_tap(vec!["remote", "--forse"]).unwrap_err();
+        assert_eq!(
+            error,
+            Error::Misspelled {
+                given: "--forse".to_string(),
+                suggestion: "--force".to_string()
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "unknown flag `--forse`, did you mean `--force`?"
+        );
+        assert_eq!(
+            parser.try_tap(vec!["remote", "--verbos"]).unwrap_err(),
+            Error::Misspelled {
+                given: "--verbos".to_string(),
+                suggestion: "--verbose".to_string()
+            }
+        );
+        assert_eq!(
+            parser.try_tap(vec!["--force"]).unwrap_err(),
+            Error::UnknownFlag("--force".to_string())
+        );
+    }
+
+    #[test]
+    fn completion() {
+        let mut parser = Parser::new();
+        parser
+            .help()
+            .add_flag(Flag::new("format", 'f', "format", true).possible_values(["json", "text"]))
+            .subcommand(
+                SubCommandConfig::with_name("remote")
+                    .description("Manage remotes")
+                    .alias("r")
+                    .subcommand(SubCommandConfig::with_name("add").flag("url", 'u', "url", true)),
+            );
+        parser.meta(Meta::new().name("app"));
+        let bash = parser.render_completion(Shell::Bash).unwrap();
+        assert!(bash.contains("            \"app,remote\"|\"app,r\") cmd=\"app__remote\" ;;\n"));
+        assert!(bash.contains("            \"app__remote,add\") cmd=\"app__remote__add\" ;;\n"));
+        assert!(bash.contains("            opts=\"-h --help -f --format remote r\"\n"));
+        assert!(bash.contains("-f|--format) COMPREPLY=($(compgen -W \"json text\" -- \"${cur}\"))"));
+        assert!(bash.contains("-u|--url) COMPREPLY=($(compgen -f -- \"${cur}\")); return 0 ;;"));
+        assert!(bash.ends_with("complete -F _app app\n"));
+        let zsh = parser.render_completion(Shell::Zsh).unwrap();
+        assert!(zsh.starts_with("#compdef app\n"));
+        assert!(zsh.contains("'(-f --format)'{-f,--format}'[]:FORMAT:(json text)' \\\n"));
+        assert!(zsh.contains("local commands=('remote:Manage remotes' 'r:Manage remotes')"));
+        assert!(zsh.contains("                remote|r) _app__remote ;;\n"));
+        assert!(zsh.contains("\n_app__remote__add() {\n"));
+        let fish = parser.render_completion(Shell::Fish).unwrap();
+        assert!(fish
+            .contains("complete -c app -n '__fish_use_subcommand' -f -a r -d 'Manage remotes'\n"));
+        assert!(fish.contains(
+            "complete -c app -n '__fish_use_subcommand' -s f -l format -r -f -a 'json text'\n"
+        ));
+        assert!(fish.contains(
+            "complete -c app -n '__fish_seen_subcommand_from remote r; and \
+             __fish_seen_subcommand_from add' -s u -l url -r\n"
+        ));
+        let mut parser = Parser::new();
+        parser.subcommand(SubCommandConfig::with_name("café"));
+        parser.meta(Meta::new().name("app"));
+        let bash = parser.render_completion(Shell::Bash).unwrap();
+        assert!(bash.contains("            \"app,café\") cmd=\"app__caf_\" ;;\n"));
+        assert!(bash.contains("        app__caf_)\n"));
+        let zsh = parser.render_completion(Shell::Zsh).unwrap();
+        assert!(zsh.contains("                café) _app__caf_ ;;\n"));
+        assert!(zsh.contains("\n_app__caf_() {\n"));
+    }
+
+    #[test]
+    fn man_pages() {
+        let mut parser = Parser::new();
+        parser
+            .meta(Meta::new().name("app").version("1.2.0"))
+            .description("A test binary")
+            .help()
+            .add_flag(
+                Flag::new("format", 'f', "format", true)
+                    .default_value("text")
+                    .description("Output format"),
+            )
+            .subcommand(
+                SubCommandConfig::with_name("remote")
+                    .description("Manage remotes")
+                    .alias("r")
+                    .subcommand(
+                        SubCommandConfig::with_name("add")
+                            .description("Add a remote")
+                            .positional("name", true),
+                    ),
+            );
+        let page = parser.render_man().unwrap();
+        assert_eq!(
+            page,
+            ".TH APP 1 \"\" \"app 1.2.0\"\n\
+             .SH NAME\n\
+             app \\- A test binary\n\
+             .SH SYNOPSIS\n\
+             \\fBapp [OPTIONS] [COMMAND]\\fR\n\
+             .SH DESCRIPTION\n\
+             A test binary\n\
+             .SH OPTIONS\n\
+             .TP\n\
+             \\fB\\-h\\fR, \\fB\\-\\-help\\fR\n\
+             Print help\n\
+             .TP\n\
+             \\fB\\-f\\fR, \\fB\\-\\-format\\fR \\fIFORMAT\\fR\n\
+             Output format [default: text]\n\
+             .SH COMMANDS\n\
+             .TP\n\
+             \\fBremote\\fR\n\
+             Manage remotes [aliases: r]\n"
+        );
+        let pages = parser.render_man_pages().unwrap();
+        let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, &["app.1", "app-remote.1", "app-remote-add.1"]);
+        let (_, page) = &pages[2];
+        assert!(page.starts_with(".TH APP\\-REMOTE\\-ADD 1 \"\" \"app 1.2.0\"\n"));
+        assert!(page.contains("app\\-remote\\-add \\- Add a remote\n"));
+        assert!(page.contains("\\fBapp remote add <NAME>\\fR\n"));
+    }
+
+    #[test]
+    fn markdown() {
+        let mut parser = Parser::new();
+        parser
+            .meta(Meta::new().name("app"))
+            .description("A test binary")
+            .help()
+            .subcommand(
+                SubCommandConfig::with_name("remote")
+                    .description("Manage remotes")
+                    .alias("r")
+                    .alias("rem")
+                    .subcommand(
+                        SubCommandConfig::with_name("add")
+                            .flag("url", 'u', "url", true)
+                            .positional("name", true),
+                    ),
+            );
+        assert_eq!(
+            parser.render_markdown().unwrap(),
+            "# app\n\
+             \n\
+             A test binary\n\
+             \n\
+             Usage: `app [OPTIONS] [COMMAND]`\n\
+             \n\
+             | Short | Long | Takes value | Description |\n\
+             |-------|------|-------------|-------------|\n\
+             | `-h` | `--help` | no | Print help |\n\
+             \n\
+             Commands:\n\
+             \n\
+             - [`remote`](#app-remote): Manage remotes\n\
+             \n\
+             ## app remote\n\
+             \n\
+             Manage remotes\n\
+             \n\
+             Usage: `app remote [COMMAND]`\n\
+             \n\
+             Aliases: `r`, `rem`\n\
+             \n\
+             Commands:\n\
+             \n\
+             - [`add`](#app-remote-add)\n\
+             \n\
+             ### app remote add\n\
+             \n\
+             Usage: `app remote add [OPTIONS] <NAME>`\n\
+             \n\
+             | Argument | Required | Description |\n\
+             |----------|----------|-------------|\n\
+             | `<NAME>` | yes |  |\n\
+             \n\
+             | Short | Long | Takes value | Description |\n\
+             |-------|------|-------------|-------------|\n\
+             | `-u` | `--url` | yes |  |\n"
+        );
+    }
+
+    #[test]
+    fn meta() {
+        let mut parser = Parser::new();
+        parser
+            .meta(
+                Meta::new()
+                    .name("app")
+                    .version("1.2.0")
+                    .authors("Jane:John")
+                    .license("MPL-2.0"),
+            )
+            .help()
+            .version()
+            .license()
+            .subcommand(
+                SubCommandConfig::with_name("plugin")
+                    .with_version("0.3.1")
+                    .version(),
+            );
+        assert_eq!(
+            parser.try_tap(vec!["-v"]).unwrap_err(),
+            Error::Version("app 1.2.0\n".to_string())
+        );
+        assert_eq!(
+            parser.try_tap(vec!["plugin", "--version"]).unwrap_err(),
+            Error::Version("app plugin 0.3.1\n".to_string())
+        );
+        assert_eq!(
+            parser.try_tap(vec!["-L"]).unwrap_err(),
+            Error::License("MPL-2.0\n".to_string())
+        );
+        let help = parser.render_help(&[]).unwrap();
+        assert!(help.starts_with("app 1.2.0\nJane, John\n\nUsage: app [OPTIONS] [COMMAND]\n"));
+        let mut parser = Parser::new();
+        parser.add_flag(Flag::new("version", 'r', "release", true));
+        let parsed = parser.try_tap(vec!["--release", "1.2"]).unwrap();
+        assert_eq!(parsed.value_of("version").as_deref(), Some("1.2"));
+        let meta = crate_meta!();
+        assert_eq!(meta.name.as_deref(), Some("tap"));
+        assert_eq!(meta.license.as_deref(), Some("MPL-2.0"));
+    }
+
+    #[test]
+    fn owned_specs() {
+        // as read from a plugin manifest, dropped once the parser is built
+        fn plugin(name: &str, flag: &str, choices: &[&str]) -> SubCommandConfig<'static> {
+            let choices: Vec<String> = choices.iter().map(|c| c.to_string()).collect();
+            SubCommandConfig::with_name(name.to_string())
+                .alias(format!("{}-plugin", name))
+                .description(format!("Run the {} plugin", name))
+                .add_flag(
+                    Flag::new(flag.to_string(), 't', flag.to_string(), true)
+                        .possible_values(choices),
+                )
+                .flag("force", 'f', "force", false)
+                .rule(Rule::conflicts(flag.to_string(), String::from("force")))
+        }
+        let version = format!("{}.{}", 1, 4);
+        let mut parser: Parser<'static> = Parser::new();
+        parser
+            .meta(Meta::new().name(String::from("host")).version(version))
+            .version()
+            .description(String::from("Plugin host"))
+            .subcommand(plugin("deploy", "target", &["dev", "prod"]))
+            .subcommand(plugin("build", "profile", &["debug", "release"]));
+        let parsed = parser.tap(vec!["deploy-plugin", "--target", "prod"]);
+        assert_eq!(parsed.subcommands(), &["deploy"]);
+        assert_eq!(parsed.value_of("target").as_deref(), Some("prod"));
+        let help = parser.render_help(&[]).unwrap();
+        assert!(help.starts_with("host 1.4\n"));
+        assert!(help.contains("Plugin host"));
+        assert!(help.contains("deploy  Run the deploy plugin [aliases: deploy-plugin]"));
+        assert!(matches!(
+            parser.try_tap(vec!["build", "-t", "prod"]),
+            Err(Error::InvalidChoice { .. })
+        ));
+        assert_eq!(
+            parser
+                .try_tap(vec!["deploy", "-t", "dev", "-f"])
+                .unwrap_err(),
+            Error::Conflict("--target".to_string(), "--force".to_string())
+        );
+    }
+
+    #[test]
+    fn long_flags_errors() {
+        let mut parser = Parser::new();
+        parser.verbose().flag("output", 'o', "output", true);
+        assert_eq!(
+            parser.try_tap(vec!["--verbose=yes"]).unwrap_err(),
+            Error::UnexpectedValue {
+                flag: "--verbose".to_string(),
+                value: "yes".to_string()
+            }
+        );
+        assert_eq!(
+            parser.try_tap(vec!["--output"]).unwrap_err(),
+            Error::MissingValue("--output".to_string())
+        );
+        for args in [vec!["--output="], vec!["--output", ""], vec!["-o", ""]] {
+            let parsed = parser.try_tap(args).unwrap();
+            assert_eq!(parsed.value_of("output").as_deref(), Some(""));
+        }
+        assert_eq!(
+            parser.try_tap(vec!["-o"]).unwrap_err(),
+            Error::MissingValue("-o".to_string())
+        );
+    }
+
+    #[test]
+    fn spec_errors() {
+        assert_eq!(
+            SubCommandConfig::try_with_name("not valid").unwrap_err(),
+            Error::InvalidName("not valid".to_string())
+        );
+        assert!(SubCommandConfig::try_with_name("").is_err());
+        let mut parser = Parser::new();
+        parser.subcommand(SubCommandConfig::with_name("in-valid"));
+        assert_eq!(
+            parser.try_tap(vec![]).unwrap_err(),
+            Error::InvalidName("in-valid".to_string())
+        );
+        let mut parser = Parser::new();
+        parser.subcommand(SubCommandConfig::with_name("sub"));
+        assert!(parser
+            .try_subcommand(SubCommandConfig::with_name("sub"))
+            .is_err());
+        let mut parser = Parser::new();
+        parser.subcommand(
+            SubCommandConfig::with_name("sub")
+                .subcommand(SubCommandConfig::with_name("twice"))
+                .subcommand(SubCommandConfig::with_name("twice")),
+        );
+        let error = parser.try_tap(vec![]).unwrap_err();
+        assert!(error.is_spec_error());
+        assert_eq!(
+            error,
+            Error::DuplicateSubCommand {
+                name: "twice".to_string(),
+                parent: Some("sub".to_string())
+            }
+        );
+        let sub = SubCommandConfig::with_name("sub").subcommand(SubCommandConfig::with_name("a"));
+        assert!(sub
+            .try_subcommand(SubCommandConfig::with_name("a"))
+            .is_err());
+        let mut parser = Parser::new();
+        parser
+            .subcommand(SubCommandConfig::with_name("one").alias("x"))
+            .subcommand(SubCommandConfig::with_name("two").alias("x"));
+        assert_eq!(
+            parser.try_tap(vec!["x"]).unwrap_err(),
+            Error::DuplicateSubCommand {
+                name: "x".to_string(),
+                parent: None
+            }
+        );
+        let mut parser = Parser::new();
+        parser.subcommand(SubCommandConfig::with_name("one"));
+        assert!(parser
+            .try_subcommand(SubCommandConfig::with_name("two").alias("one"))
+            .is_err());
+    }
+}
@@ -0,0 +1,132 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::graph::Graph;
+use crate::help::{self, Level};
+use crate::{Arg, Error, Meta};
+
+/// Renders the man page of the binary and, when `nested`, one page per
+/// subcommand. Each page comes with its file name, `app-remote-add.1`.
+pub(crate) fn render(
+    graph: &Graph<Arg>,
+    bin: &str,
+    description: Option<&str>,
+    meta: &Meta,
+    env_prefix: Option<&str>,
+    nested: bool,
+) -> Result<Vec<(String, String)>, Error> {
+    let mut levels = help::levels(graph)?;
+    if !nested {
+        levels.truncate(1);
+    }
+    let mut pages = vec![];
+    for level in &levels {
+        let name = level.names(bin).join("-");
+        let description = match level.path.last() {
+            Some(subcommand) => subcommand.description.as_deref(),
+            None => description,
+        };
+        let page = page(graph, level, &name, bin, description, meta, env_prefix)?;
+        pages.push((format!("{}.1", name), page));
+    }
+    Ok(pages)
+}
+
+fn page(
+    graph: &Graph<Arg>,
+    level: &Level,
+    name: &str,
+    bin: &str,
+    description: Option<&str>,
+    meta: &Meta,
+    env_prefix: Option<&str>,
+) -> Result<String, Error> {
+    let source = match &meta.version {
+        Some(version) => format!("{} {}", bin, version),
+        None => bin.to_string(),
+    };
+    let mut page = format!(
+        ".TH {} 1 \"\" \"{}\"\n",
+        escape(&name.to_uppercase()),
+        escape(&source)
+    );
+    page.push_str(".SH NAME\n");
+    match description {
+        Some(description) => {
+            page.push_str(&format!("{} \\- {}\n", escape(name), escape(description)))
+        }
+        None => page.push_str(&format!("{}\n", escape(name))),
+    }
+    page.push_str(".SH SYNOPSIS\n");
+    let usage = help::usage(graph, level.index, bin)?;
+    page.push_str(&format!("\\fB{}\\fR\n", escape(&usage)));
+    if let Some(description) = description {
+        page.push_str(".SH DESCRIPTION\n");
+        page.push_str(&format!("{}\n", text(description)));
+    }
+    if !level.positionals.is_empty() {
+        page.push_str(".SH ARGUMENTS\n");
+        for positional in &level.positionals {
+            let usage = help::positional_usage(positional);
+            page.push_str(&format!(".TP\n\\fI{}\\fR\n", escape(&usage)));
+            if let Some(description) = &positional.description {
+                page.push_str(&format!("{}\n", text(description)));
+            }
+        }
+    }
+    if !level.flags.is_empty() {
+        page.push_str(".SH OPTIONS\n");
+        for (flag, depth) in &level.flags {
+            let mut item = format!(
+                "\\fB\\-{}\\fR, \\fB\\-\\-{}\\fR",
+                flag.short,
+                escape(&flag.long)
+            );
+            if flag.takes_arg {
+                item.push_str(&format!(" \\fI{}\\fR", escape(&flag.name.to_uppercase())));
+            }
+            page.push_str(&format!(".TP\n{}\n", item));
+            let details = help::flag_details(flag, &level.path[..*depth], env_prefix);
+            if !details.is_empty() {
+                page.push_str(&format!("{}\n", text(&details)));
+            }
+        }
+    }
+    if !level.subcommands.is_empty() {
+        page.push_str(".SH COMMANDS\n");
+        for subcommand in &level.subcommands {
+            page.push_str(&format!(".TP\n\\fB{}\\fR\n", escape(&subcommand.name)));
+            let details = help::subcommand_details(subcommand);
+            if !details.is_empty() {
+                page.push_str(&format!("{}\n", text(&details)));
+            }
+        }
+    }
+    if let Some(authors) = meta.authors.as_ref().filter(|_| level.path.is_empty()) {
+        page.push_str(".SH AUTHORS\n");
+        page.push_str(&format!("{}\n", text(&authors.replace(':', ", "))));
+    }
+    Ok(page)
+}
+
+/// Escapes backslashes and hyphens, roff renders a bare `-` as a hyphen
+/// rather than a minus.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+/// Escapes a paragraph, a line starting with `.` or `'` would be read as a
+/// request.
+fn text(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = escape(line);
+            match line.starts_with('.') || line.starts_with('\'') {
+                true => format!("\\&{}", line),
+                false => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
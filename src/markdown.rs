@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::graph::Graph;
+use crate::help;
+use crate::{Arg, Error};
+
+/// Renders a Markdown reference with a section per subcommand, nested by
+/// heading level.
+pub(crate) fn render(
+    graph: &Graph<Arg>,
+    bin: &str,
+    description: Option<&str>,
+    env_prefix: Option<&str>,
+) -> Result<String, Error> {
+    let mut sections = vec![];
+    for level in help::levels(graph)? {
+        let names = level.names(bin);
+        let title = names.join(" ");
+        let mut section = format!("{} {}\n", "#".repeat(names.len().min(6)), title);
+        let description = match level.path.last() {
+            Some(subcommand) => subcommand.description.as_deref(),
+            None => description,
+        };
+        if let Some(description) = description {
+            section.push_str(&format!("\n{}\n", description));
+        }
+        let usage = help::usage(graph, level.index, bin)?;
+        section.push_str(&format!("\nUsage: `{}`\n", usage));
+        if let Some(subcommand) = level.path.last().filter(|sub| !sub.aliases.is_empty()) {
+            let aliases: Vec<String> = subcommand
+                .aliases
+                .iter()
+                .map(|alias| format!("`{}`", alias))
+                .collect();
+            section.push_str(&format!("\nAliases: {}\n", aliases.join(", ")));
+        }
+        if !level.positionals.is_empty() {
+            section.push_str("\n| Argument | Required | Description |\n");
+            section.push_str("|----------|----------|-------------|\n");
+            for positional in &level.positionals {
+                section.push_str(&format!(
+                    "| `{}` | {} | {} |\n",
+                    help::positional_usage(positional),
+                    yes_no(positional.required),
+                    cell(positional.description.as_deref().unwrap_or_default())
+                ));
+            }
+        }
+        if !level.flags.is_empty() {
+            section.push_str("\n| Short | Long | Takes value | Description |\n");
+            section.push_str("|-------|------|-------------|-------------|\n");
+            for (flag, depth) in &level.flags {
+                let details = help::flag_details(flag, &level.path[..*depth], env_prefix);
+                section.push_str(&format!(
+                    "| `-{}` | `--{}` | {} | {} |\n",
+                    flag.short,
+                    flag.long,
+                    yes_no(flag.takes_arg),
+                    cell(&details)
+                ));
+            }
+        }
+        if !level.subcommands.is_empty() {
+            section.push_str("\nCommands:\n\n");
+            for subcommand in &level.subcommands {
+                // GitHub style anchor of the child section title
+                let anchor = format!("{}-{}", names.join("-"), subcommand.name).to_lowercase();
+                let mut item = format!("- [`{}`](#{})", subcommand.name, anchor);
+                if let Some(description) = &subcommand.description {
+                    item.push_str(&format!(": {}", description));
+                }
+                section.push_str(&format!("{}\n", item));
+            }
+        }
+        sections.push(section);
+    }
+    Ok(sections.join("\n"))
+}
+
+fn yes_no(value: bool) -> &'static str {
+    match value {
+        true => "yes",
+        false => "no",
+    }
+}
+
+/// Escapes a table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
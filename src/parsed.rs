@@ -0,0 +1,360 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::graph::Graph;
+use crate::help;
+use crate::{file_name, Arg, ArgType, Error};
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Where the value of a flag comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    CommandLine,
+    /// The flag was read from the environment, holds the variable name.
+    Env(String),
+    /// The flag was not given, its value is the declared default.
+    Default,
+}
+
+/// A flag or a declared positional matched on the command line.
+#[derive(Debug)]
+pub struct Opt<'a> {
+    name: Cow<'a, str>,
+    /// How the user wrote it, `--output` or `<INPUT>`, for errors.
+    label: String,
+    values: Vec<OsString>,
+    occurrences: usize,
+    source: Source,
+    level: usize,
+}
+
+impl<'a> Opt<'a> {
+    fn new(name: Cow<'a, str>, label: String, arg: &Arg, level: usize) -> Self {
+        Opt {
+            name,
+            label,
+            values: arg.values.clone(),
+            occurrences: arg.occurrences,
+            source: arg.source.clone(),
+            level,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value given to the flag, invalid UTF-8 is replaced, see
+    /// `value_os` for the raw value.
+    pub fn value(&self) -> Option<Cow<'_, str>> {
+        self.value_os().map(OsStr::to_string_lossy)
+    }
+
+    pub fn value_os(&self) -> Option<&OsStr> {
+        self.values.last().map(OsString::as_os_str)
+    }
+
+    /// All the values given, in command line order.
+    pub fn values(&self) -> Vec<Cow<'_, str>> {
+        lossy(&self.values)
+    }
+
+    pub fn values_os(&self) -> &[OsString] {
+        &self.values
+    }
+
+    /// The value converted to `T`, failing with `Error::InvalidValue` when
+    /// it does not parse.
+    pub fn parse<T>(&self) -> Result<Option<T>, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.value_os().map(|value| self.convert(value)).transpose()
+    }
+
+    /// Every value converted to `T`, see `parse`.
+    pub fn parse_all<T>(&self) -> Result<Vec<T>, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.values
+            .iter()
+            .map(|value| self.convert(value))
+            .collect()
+    }
+
+    fn convert<T>(&self, value: &OsStr) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let error = |reason: String| Error::InvalidValue {
+            arg: self.label.clone(),
+            value: value.to_string_lossy().into_owned(),
+            reason,
+        };
+        match value.to_str() {
+            Some(text) => text.parse().map_err(|e: T::Err| error(e.to_string())),
+            None => Err(error("invalid UTF-8".to_string())),
+        }
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// How many times the flag was given at its level, `-VVV` counting
+    /// three.
+    pub fn occurrences(&self) -> usize {
+        self.occurrences
+    }
+
+    /// The subcommand depth the flag was matched at, `0` being the binary
+    /// itself and `n` the n-th entry of `Parsed::subcommands`.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+}
+
+/// The result of `Parser::tap`.
+#[derive(Debug, Default)]
+pub struct Parsed<'a> {
+    pub(crate) bin: Option<OsString>,
+    subcommands: Vec<Cow<'a, str>>,
+    flags: Vec<Opt<'a>>,
+    positionals: Vec<Opt<'a>>,
+    args: Vec<OsString>,
+    terminator: Option<usize>,
+    unknown: Vec<OsString>,
+}
+
+impl<'a> Parsed<'a> {
+    /// The binary as given by the first process argument, only known when
+    /// parsing with `Parser::tap_env`.
+    pub fn bin(&self) -> Option<&OsStr> {
+        self.bin.as_deref()
+    }
+
+    /// The file name of the binary, for display.
+    pub fn bin_name(&self) -> Option<String> {
+        self.bin.as_deref().map(file_name)
+    }
+
+    /// The path of matched subcommands, from the outermost to the innermost.
+    /// Aliases are resolved to the subcommand name.
+    pub fn subcommands(&self) -> &[Cow<'a, str>] {
+        &self.subcommands
+    }
+
+    /// The innermost matched subcommand.
+    pub fn subcommand(&self) -> Option<&str> {
+        self.subcommands.last().map(|name| &**name)
+    }
+
+    pub fn flags(&self) -> &[Opt<'a>] {
+        &self.flags
+    }
+
+    /// The flag with the given name, the deepest match wins when the same
+    /// name is declared at several levels.
+    pub fn flag(&self, name: &str) -> Option<&Opt<'a>> {
+        self.flags.iter().rev().find(|opt| opt.name == name)
+    }
+
+    /// The flag with the given name matched at the subcommand depth
+    /// `level`, see `Opt::level`.
+    pub fn flag_at(&self, name: &str, level: usize) -> Option<&Opt<'a>> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|opt| opt.name == name && opt.level == level)
+    }
+
+    /// Whether the flag was given, a default value does not count.
+    pub fn is_present(&self, name: &str) -> bool {
+        self.flags
+            .iter()
+            .any(|opt| opt.name == name && opt.source != Source::Default)
+    }
+
+    /// How many times the flag was given, summed over every level.
+    pub fn occurrences(&self, name: &str) -> usize {
+        self.flags
+            .iter()
+            .filter(|opt| opt.name == name)
+            .map(Opt::occurrences)
+            .sum()
+    }
+
+    /// The verbosity level, the number of times the built-in `verbose` flag
+    /// was given.
+    pub fn verbosity(&self) -> usize {
+        self.occurrences("verbose")
+    }
+
+    pub fn value_of(&self, name: &str) -> Option<Cow<'_, str>> {
+        self.flag(name).and_then(|opt| opt.value())
+    }
+
+    pub fn value_of_os(&self, name: &str) -> Option<&OsStr> {
+        self.flag(name).and_then(|opt| opt.value_os())
+    }
+
+    /// The value of a flag converted to `T`, see `Opt::parse`.
+    pub fn value_as<T>(&self, name: &str) -> Result<Option<T>, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.flag(name).map_or(Ok(None), Opt::parse)
+    }
+
+    /// Every value of a flag converted to `T`, see `Opt::parse_all`.
+    pub fn values_as<T>(&self, name: &str) -> Result<Vec<T>, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.flag(name).map_or(Ok(vec![]), Opt::parse_all)
+    }
+
+    /// The declared positionals that were given.
+    pub fn positionals(&self) -> &[Opt<'a>] {
+        &self.positionals
+    }
+
+    /// The declared positional with the given name, the deepest match wins
+    /// when the same name is declared at several levels.
+    pub fn positional(&self, name: &str) -> Option<&Opt<'a>> {
+        self.positionals.iter().rev().find(|opt| opt.name == name)
+    }
+
+    /// The declared positional with the given name matched at the subcommand
+    /// depth `level`.
+    pub fn positional_at(&self, name: &str, level: usize) -> Option<&Opt<'a>> {
+        self.positionals
+            .iter()
+            .find(|opt| opt.name == name && opt.level == level)
+    }
+
+    /// The value of a declared positional converted to `T`, see
+    /// `Opt::parse`.
+    pub fn positional_as<T>(&self, name: &str) -> Result<Option<T>, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.positional(name).map_or(Ok(None), Opt::parse)
+    }
+
+    /// Every value of a declared positional converted to `T`, for variadic
+    /// ones.
+    pub fn positional_values_as<T>(&self, name: &str) -> Result<Vec<T>, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.positional(name).map_or(Ok(vec![]), Opt::parse_all)
+    }
+
+    /// The positional arguments, in command line order, declared or not. Invalid UTF-8 is
+    /// replaced, see `args_os` for the raw arguments.
+    pub fn args(&self) -> Vec<Cow<'_, str>> {
+        lossy(&self.args)
+    }
+
+    pub fn args_os(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// The position in `args` of the first argument following `--`, if the
+    /// terminator was given.
+    pub fn terminator(&self) -> Option<usize> {
+        self.terminator
+    }
+
+    /// The unknown flags collected in `Mode::Lenient`, as given on the
+    /// command line, so they can be forwarded.
+    pub fn unknown(&self) -> Vec<Cow<'_, str>> {
+        lossy(&self.unknown)
+    }
+
+    pub fn unknown_os(&self) -> &[OsString] {
+        &self.unknown
+    }
+
+    /// The arguments following `--`, untouched by the parser.
+    pub fn trailing(&self) -> Vec<Cow<'_, str>> {
+        lossy(self.trailing_os())
+    }
+
+    pub fn trailing_os(&self) -> &[OsString] {
+        match self.terminator {
+            Some(i) => &self.args[i..],
+            None => &[],
+        }
+    }
+}
+
+fn lossy(values: &[OsString]) -> Vec<Cow<'_, str>> {
+    values.iter().map(|value| value.to_string_lossy()).collect()
+}
+
+impl<'a> From<&Graph<Arg<'a>>> for Parsed<'a> {
+    fn from(graph: &Graph<Arg<'a>>) -> Self {
+        let mut parsed = Parsed::default();
+        let mut level = None;
+        loop {
+            let mut next_level = None;
+            // indexes come from the graph itself so they cannot be invalid,
+            // nodes added while parsing are in command line order
+            let children = graph.ordered_successors(level).unwrap_or_default();
+            for index in children {
+                let arg = &graph.nodes[index].data;
+                if !arg.found {
+                    continue;
+                }
+                match &arg.kind {
+                    ArgType::Flag(flag) => {
+                        let depth = parsed.subcommands.len();
+                        let label = format!("--{}", flag.long);
+                        parsed
+                            .flags
+                            .push(Opt::new(flag.name.clone(), label, arg, depth));
+                    }
+                    ArgType::Positional(positional) => {
+                        let depth = parsed.subcommands.len();
+                        let label = help::positional_usage(positional);
+                        parsed.positionals.push(Opt::new(
+                            positional.name.clone(),
+                            label,
+                            arg,
+                            depth,
+                        ));
+                    }
+                    ArgType::SubCommand(subcommand) => {
+                        next_level = Some((index, subcommand.name.clone()))
+                    }
+                    ArgType::Argument(value) => parsed.args.push(value.clone()),
+                    ArgType::Over => parsed.terminator = Some(parsed.args.len()),
+                    ArgType::Rule(_) => {}
+                    ArgType::UnknownFlag(value) => parsed.unknown.push(value.clone()),
+                }
+            }
+            match next_level {
+                Some((index, name)) => {
+                    parsed.subcommands.push(name);
+                    level = Some(index);
+                }
+                None => break,
+            }
+        }
+        parsed
+    }
+}
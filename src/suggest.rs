@@ -0,0 +1,61 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/// The candidate closest to `given`, if close enough to be a typo: one edit
+/// away for short tokens, two from four characters on.
+pub(crate) fn closest<'c, I>(given: &str, candidates: I) -> Option<&'c str>
+where
+    I: IntoIterator<Item = &'c str>,
+{
+    let limit = match given.chars().count() {
+        0..=1 => return None,
+        2..=3 => 1,
+        _ => 2,
+    };
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(given, candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("remote", "remote"), 0);
+        assert_eq!(distance("remtoe", "remote"), 2);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn closest_candidate() {
+        let candidates = vec!["remote", "rebase", "init"];
+        assert_eq!(closest("remot", candidates.clone()), Some("remote"));
+        assert_eq!(closest("rebsae", candidates.clone()), Some("rebase"));
+        assert_eq!(closest("inti", candidates.clone()), Some("init"));
+        assert_eq!(closest("push", candidates.clone()), None);
+        assert_eq!(closest("x", candidates), None);
+    }
+}
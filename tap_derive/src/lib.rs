@@ -0,0 +1,562 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! `#[derive(Tap)]` builds a `tap::Parser` from a struct and fills the struct
+//! back from the parse result.
+//!
+//! On a struct with named fields:
+//! - `bool` fields are flags taking no value, `#[tap(count)]` on a `usize`
+//!   gives the number of occurrences;
+//! - `Option<T>`, `Vec<T>` and `T` fields are flags taking a value converted
+//!   through `FromStr`, a `T` one is required unless it has a default;
+//! - `#[tap(positional)]` fields are positionals, required for `T`,
+//!   optional for `Option<T>` and variadic for `Vec<T>`;
+//! - a `#[tap(subcommand)]` field of type `Option<E>` holds the matched
+//!   variant of an enum deriving `Tap`.
+//!
+//! The generated parser runs in `Mode::Strict`, unknown flags and extra
+//! arguments are errors.
+//!
+//! A flag's short name defaults to the first letter of its field, two flags
+//! of a struct sharing one, or one using the `-h` of help, fail to compile.
+//!
+//! Field attributes: `short = 'c'`, `long = "name"`, `name = "name"`,
+//! `default = "value"` on flags taking a value, `env = "VAR"` and `global`,
+//! a global `Vec<T>` collecting the values of every level. Doc comments
+//! become descriptions. On an enum, each variant is a subcommand named after it in
+//! snake case, either a unit variant or one holding a struct deriving `Tap`,
+//! and takes `name = "name"` and `alias = "alias"`.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DataEnum, DataStruct, DeriveInput, Expr, Fields,
+    GenericArgument, Ident, Lit, LitChar, LitStr, Meta, PathArguments, Type,
+};
+
+#[proc_macro_derive(Tap, attributes(tap))]
+pub fn derive_tap(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let result = match &input.data {
+        Data::Struct(data) => derive_struct(&input, data),
+        Data::Enum(data) => derive_enum(&input, data),
+        Data::Union(_) => Err(syn::Error::new_spanned(&input, "unions are not supported")),
+    };
+    result.unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// What `#[tap(..)]` attributes and doc comments say about an item.
+#[derive(Default)]
+struct Attrs {
+    short: Option<LitChar>,
+    long: Option<LitStr>,
+    name: Option<LitStr>,
+    default: Option<LitStr>,
+    env: Option<LitStr>,
+    aliases: Vec<LitStr>,
+    description: Option<String>,
+    positional: bool,
+    subcommand: bool,
+    count: bool,
+    global: bool,
+}
+
+impl Attrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Attrs::default();
+        let mut doc = vec![];
+        for attr in attrs {
+            if attr.path().is_ident("doc") {
+                if let Meta::NameValue(meta) = &attr.meta {
+                    if let Expr::Lit(expr) = &meta.value {
+                        if let Lit::Str(line) = &expr.lit {
+                            doc.push(line.value().trim().to_string());
+                        }
+                    }
+                }
+                continue;
+            }
+            if !attr.path().is_ident("tap") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+                if path.is_ident("short") {
+                    parsed.short = Some(meta.value()?.parse()?);
+                } else if path.is_ident("long") {
+                    parsed.long = Some(meta.value()?.parse()?);
+                } else if path.is_ident("name") {
+                    parsed.name = Some(meta.value()?.parse()?);
+                } else if path.is_ident("default") {
+                    parsed.default = Some(meta.value()?.parse()?);
+                } else if path.is_ident("env") {
+                    parsed.env = Some(meta.value()?.parse()?);
+                } else if path.is_ident("alias") {
+                    parsed.aliases.push(meta.value()?.parse()?);
+                } else if path.is_ident("description") {
+                    let description: LitStr = meta.value()?.parse()?;
+                    parsed.description = Some(description.value());
+                } else if path.is_ident("positional") {
+                    parsed.positional = true;
+                } else if path.is_ident("subcommand") {
+                    parsed.subcommand = true;
+                } else if path.is_ident("count") {
+                    parsed.count = true;
+                } else if path.is_ident("global") {
+                    parsed.global = true;
+                } else {
+                    return Err(meta.error("unknown tap attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if parsed.description.is_none() && !doc.is_empty() {
+            parsed.description = Some(doc.join(" ").trim().to_string());
+        }
+        Ok(parsed)
+    }
+
+    /// `.description("..")` when there is one.
+    fn description(&self) -> TokenStream2 {
+        match &self.description {
+            Some(description) => quote!(.description(#description)),
+            None => quote!(),
+        }
+    }
+}
+
+/// The type wrapped by `Option` or `Vec`, named by `wrapper`.
+fn wrapped<'t>(ty: &'t Type, wrapper: &str) -> Option<&'t Type> {
+    let path = match ty {
+        Type::Path(path) if path.qself.is_none() => &path.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(GenericArgument::Type(inner)) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident("bool"))
+}
+
+/// `AddRemote` to `add_remote`.
+fn snake_case(ident: &Ident) -> String {
+    let mut name = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.extend(c.to_lowercase());
+    }
+    name
+}
+
+/// The code a field contributes to the parser declaration and to the
+/// conversion of the parse result.
+struct FieldCode {
+    /// Adds the field to `target`, a `Parser` or a `SubCommandConfig`.
+    declare: TokenStream2,
+    fill: TokenStream2,
+    /// The short name of a flag.
+    short: Option<char>,
+}
+
+fn field_code(ident: &Ident, ty: &Type, attrs: &Attrs, chained: bool) -> syn::Result<FieldCode> {
+    let field = ident.to_string();
+    let name = attrs
+        .name
+        .as_ref()
+        .map_or_else(|| field.clone(), LitStr::value);
+    let description = attrs.description();
+    // a `Parser` is built through `&mut` methods, a `SubCommandConfig` by
+    // value
+    let add = |method: TokenStream2, spec: TokenStream2| match chained {
+        true => quote!(config = config.#method(#spec);),
+        false => quote!(parser.#method(#spec);),
+    };
+    if attrs.subcommand {
+        let inner = wrapped(ty, "Option").ok_or_else(|| {
+            syn::Error::new_spanned(ty, "a subcommand field must be an `Option` of an enum")
+        })?;
+        let declare = match chained {
+            true => quote! {
+                for subcommand in <#inner>::subcommand_configs() {
+                    config = config.subcommand(subcommand);
+                }
+            },
+            false => quote! {
+                for subcommand in <#inner>::subcommand_configs() {
+                    parser.subcommand(subcommand);
+                }
+            },
+        };
+        let fill = quote!(<#inner>::from_parsed(parsed, level)?);
+        return Ok(FieldCode {
+            declare,
+            fill,
+            short: None,
+        });
+    }
+    if attrs.positional {
+        let (required, variadic, fill) = if let Some(inner) = wrapped(ty, "Vec") {
+            let fill = quote! {
+                match parsed.positional_at(#name, level) {
+                    Some(opt) => opt.parse_all::<#inner>()?,
+                    None => ::std::vec::Vec::new(),
+                }
+            };
+            (false, quote!(.variadic()), fill)
+        } else if let Some(inner) = wrapped(ty, "Option") {
+            let fill = quote! {
+                match parsed.positional_at(#name, level) {
+                    Some(opt) => opt.parse::<#inner>()?,
+                    None => None,
+                }
+            };
+            (false, quote!(), fill)
+        } else {
+            let fill = quote! {
+                match parsed.positional_at(#name, level) {
+                    Some(opt) => opt.parse::<#ty>()?,
+                    None => None,
+                }
+                .ok_or_else(|| ::tap::Error::MissingPositional(#name.to_string()))?
+            };
+            (true, quote!(), fill)
+        };
+        let spec = quote!(::tap::Positional::new(#name, #required) #variadic #description);
+        let declare = add(quote!(add_positional), spec);
+        return Ok(FieldCode {
+            declare,
+            fill,
+            short: None,
+        });
+    }
+    let long = attrs
+        .long
+        .as_ref()
+        .map_or_else(|| field.replace('_', "-"), LitStr::value);
+    let short = match &attrs.short {
+        Some(short) => short.value(),
+        None => field.chars().next().unwrap_or('_'),
+    };
+    let mut modifiers = description;
+    if let Some(env) = &attrs.env {
+        modifiers.extend(quote!(.env(#env)));
+    }
+    if attrs.global {
+        modifiers.extend(quote!(.global()));
+    }
+    // a global flag can be given below the level declaring it
+    let lookup = match attrs.global {
+        true => quote!(parsed.flag(#name)),
+        false => quote!(parsed.flag_at(#name, level)),
+    };
+    if let Some(default) = attrs
+        .default
+        .as_ref()
+        .filter(|_| attrs.count || is_bool(ty))
+    {
+        return Err(syn::Error::new_spanned(
+            default,
+            "a flag taking no value cannot have a default",
+        ));
+    }
+    let (takes_arg, fill) = if attrs.count {
+        let fill = match attrs.global {
+            true => quote!(parsed.occurrences(#name)),
+            false => quote!(#lookup.map_or(0, ::tap::Opt::occurrences)),
+        };
+        (false, fill)
+    } else if is_bool(ty) {
+        (false, quote!(#lookup.is_some()))
+    } else if let Some(inner) = wrapped(ty, "Vec") {
+        modifiers.extend(quote!(.multiple()));
+        // a global flag keeps the values given at every level, in command
+        // line order
+        let fill = match attrs.global {
+            true => quote! {{
+                let mut values = ::std::vec::Vec::new();
+                for opt in parsed.flags() {
+                    if opt.name() == #name && opt.level() >= level {
+                        values.extend(opt.parse_all::<#inner>()?);
+                    }
+                }
+                values
+            }},
+            false => quote! {
+                match #lookup {
+                    Some(opt) => opt.parse_all::<#inner>()?,
+                    None => ::std::vec::Vec::new(),
+                }
+            },
+        };
+        (true, fill)
+    } else if let Some(inner) = wrapped(ty, "Option") {
+        let fill = quote! {
+            match #lookup {
+                Some(opt) => opt.parse::<#inner>()?,
+                None => None,
+            }
+        };
+        (true, fill)
+    } else {
+        if attrs.default.is_none() {
+            modifiers.extend(quote!(.required()));
+        }
+        let label = format!("--{}", long);
+        let fill = quote! {
+            match #lookup {
+                Some(opt) => opt.parse::<#ty>()?,
+                None => None,
+            }
+            .ok_or_else(|| ::tap::Error::MissingFlag(#label.to_string()))?
+        };
+        (true, fill)
+    };
+    if let Some(default) = &attrs.default {
+        modifiers.extend(quote!(.default_value(#default)));
+    }
+    let spec = quote!(::tap::Flag::new(#name, #short, #long, #takes_arg) #modifiers);
+    let declare = add(quote!(add_flag), spec);
+    Ok(FieldCode {
+        declare,
+        fill,
+        short: Some(short),
+    })
+}
+
+fn derive_struct(input: &DeriveInput, data: &DataStruct) -> syn::Result<TokenStream2> {
+    let fields = match &data.fields {
+        Fields::Named(fields) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "only structs with named fields are supported",
+            ))
+        }
+    };
+    let ident = &input.ident;
+    let attrs = Attrs::parse(&input.attrs)?;
+    let description = attrs.description();
+    let mut declare_parser = vec![];
+    let mut declare_config = vec![];
+    let mut fills = vec![];
+    // `-h` is taken by the help flag added to every level
+    let mut shorts = vec![('h', "help".to_string())];
+    for field in fields {
+        let field_ident = field.ident.as_ref().expect("named field");
+        let attrs = Attrs::parse(&field.attrs)?;
+        let code = field_code(field_ident, &field.ty, &attrs, false)?;
+        if let Some(short) = code.short {
+            if let Some((_, taken)) = shorts.iter().find(|(known, _)| *known == short) {
+                let message = format!(
+                    "short flag `-{}` is already used by `{}`, set another one with \
+                     `#[tap(short = '..')]`",
+                    short, taken
+                );
+                return Err(match &attrs.short {
+                    Some(lit) => syn::Error::new_spanned(lit, message),
+                    None => syn::Error::new_spanned(field_ident, message),
+                });
+            }
+            shorts.push((short, field_ident.to_string()));
+        }
+        declare_parser.push(code.declare);
+        let code = field_code(field_ident, &field.ty, &attrs, true)?;
+        declare_config.push(code.declare);
+        let fill = code.fill;
+        fills.push(quote!(#field_ident: #fill));
+    }
+    let parser_description = match &attrs.description {
+        Some(description) => quote!(parser.description(#description);),
+        None => quote!(),
+    };
+    Ok(quote! {
+        impl #ident {
+            /// The parser declaring the fields, with `-h, --help`. It is
+            /// strict as the struct has no field to hold unknown tokens.
+            pub fn parser() -> ::tap::Parser<'static> {
+                let mut parser = ::tap::Parser::new();
+                parser.mode(::tap::Mode::Strict).help();
+                #parser_description
+                #(#declare_parser)*
+                parser
+            }
+
+            /// The fields declared as the subcommand `name`.
+            pub fn subcommand_config(name: &'static str) -> ::tap::SubCommandConfig<'static> {
+                let mut config = ::tap::SubCommandConfig::with_name(name).help() #description;
+                #(#declare_config)*
+                config
+            }
+
+            /// Fills the struct from the flags and positionals matched at the
+            /// subcommand depth `level`, `0` for the binary.
+            pub fn from_parsed(
+                parsed: &::tap::Parsed<'_>,
+                level: usize,
+            ) -> ::std::result::Result<Self, ::tap::Error> {
+                ::std::result::Result::Ok(#ident {
+                    #(#fills,)*
+                })
+            }
+
+            pub fn try_parse_from<I, T>(args: I) -> ::std::result::Result<Self, ::tap::Error>
+            where
+                I: ::std::iter::IntoIterator<Item = T>,
+                T: ::std::convert::Into<::std::ffi::OsString>,
+            {
+                let parsed = Self::parser().try_tap_os(args)?;
+                Self::from_parsed(&parsed, 0)
+            }
+
+            /// Parses the process arguments, printing the error and exiting
+            /// on failure.
+            pub fn parse() -> Self {
+                let parsed = Self::parser().tap_env();
+                Self::from_parsed(&parsed, 0).unwrap_or_else(|error| error.exit())
+            }
+        }
+    })
+}
+
+fn derive_enum(input: &DeriveInput, data: &DataEnum) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let mut configs = vec![];
+    let mut arms = vec![];
+    for variant in &data.variants {
+        let attrs = Attrs::parse(&variant.attrs)?;
+        let variant_ident = &variant.ident;
+        let name = attrs
+            .name
+            .as_ref()
+            .map_or_else(|| snake_case(variant_ident), LitStr::value);
+        let aliases = &attrs.aliases;
+        let description = attrs.description();
+        match &variant.fields {
+            Fields::Unit => {
+                configs.push(quote! {
+                    ::tap::SubCommandConfig::with_name(#name).help()
+                        #(.alias(#aliases))* #description
+                });
+                arms.push(quote!(#name => #ident::#variant_ident));
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let inner = &fields.unnamed[0].ty;
+                configs.push(quote! {
+                    <#inner>::subcommand_config(#name) #(.alias(#aliases))* #description
+                });
+                arms.push(quote! {
+                    #name => #ident::#variant_ident(<#inner>::from_parsed(parsed, level + 1)?)
+                });
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "a subcommand variant is either a unit one or holds a single struct",
+                ))
+            }
+        }
+    }
+    Ok(quote! {
+        impl #ident {
+            /// One subcommand per variant.
+            pub fn subcommand_configs() -> ::std::vec::Vec<::tap::SubCommandConfig<'static>> {
+                vec![#(#configs),*]
+            }
+
+            /// The variant of the subcommand matched at the subcommand depth
+            /// `level`, if any.
+            pub fn from_parsed(
+                parsed: &::tap::Parsed<'_>,
+                level: usize,
+            ) -> ::std::result::Result<::std::option::Option<Self>, ::tap::Error> {
+                let subcommand: &str = match parsed.subcommands().get(level) {
+                    Some(subcommand) => subcommand,
+                    None => return ::std::result::Result::Ok(None),
+                };
+                ::std::result::Result::Ok(Some(match subcommand {
+                    #(#arms,)*
+                    _ => return ::std::result::Result::Ok(None),
+                }))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn derive(input: DeriveInput) -> syn::Result<TokenStream2> {
+        match &input.data {
+            Data::Struct(data) => derive_struct(&input, data),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn duplicate_shorts() {
+        let error = derive(parse_quote! {
+            struct Args {
+                host: Option<String>,
+            }
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("`-h` is already used by `help`"));
+        let error = derive(parse_quote! {
+            struct Args {
+                verbose: bool,
+                version: bool,
+            }
+        })
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("`-v` is already used by `verbose`"));
+        assert!(derive(parse_quote! {
+            struct Args {
+                verbose: bool,
+                #[tap(short = 'r')]
+                version: bool,
+                #[tap(positional)]
+                vendor: Option<String>,
+            }
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn switch_defaults() {
+        let error = derive(parse_quote! {
+            struct Args {
+                #[tap(default = "true")]
+                force: bool,
+            }
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("cannot have a default"));
+        assert!(derive(parse_quote! {
+            struct Args {
+                #[tap(count, default = "1")]
+                verbose: usize,
+            }
+        })
+        .is_err());
+    }
+}
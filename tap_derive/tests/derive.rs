@@ -0,0 +1,161 @@
+use tap::Error;
+use tap_derive::Tap;
+
+/// Copies files around.
+#[derive(Tap, Debug, PartialEq)]
+struct Args {
+    /// Print more, can be repeated
+    #[tap(count, short = 'V', global)]
+    verbose: usize,
+    /// Do nothing
+    dry_run: bool,
+    #[tap(short = 'j', default = "1")]
+    jobs: u32,
+    output: Option<String>,
+    #[tap(short = 'I')]
+    include: Vec<String>,
+    #[tap(positional)]
+    input: Option<String>,
+    #[tap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Tap, Debug, PartialEq)]
+enum Command {
+    /// Manage remotes
+    #[tap(alias = "rm")]
+    RemoteManage(Remote),
+    Status,
+}
+
+#[derive(Tap, Debug, PartialEq)]
+struct Remote {
+    #[tap(positional)]
+    name: String,
+    #[tap(positional)]
+    urls: Vec<String>,
+    #[tap(long = "fetch-depth", short = 'f')]
+    depth: u32,
+}
+
+#[derive(Tap, Debug, PartialEq)]
+struct Build {
+    #[tap(global, short = 'I')]
+    include: Vec<String>,
+    #[tap(subcommand)]
+    command: Option<Step>,
+}
+
+#[derive(Tap, Debug, PartialEq)]
+enum Step {
+    Run,
+}
+
+#[derive(Tap, Debug, PartialEq)]
+struct Single {
+    verbose: bool,
+    #[tap(positional)]
+    file: Option<String>,
+}
+
+#[test]
+fn structs() {
+    let args = Args::try_parse_from(vec!["-VV", "-d", "-o", "out", "-I", "a", "-I", "b", "in"]);
+    assert_eq!(
+        args,
+        Ok(Args {
+            verbose: 2,
+            dry_run: true,
+            jobs: 1,
+            output: Some("out".to_string()),
+            include: vec!["a".to_string(), "b".to_string()],
+            input: Some("in".to_string()),
+            command: None,
+        })
+    );
+    let args = Args::try_parse_from(vec!["--jobs", "4"]).unwrap();
+    assert_eq!(args.jobs, 4);
+    assert_eq!(args.input, None);
+    assert!(matches!(
+        Args::try_parse_from(vec!["-j", "many"]),
+        Err(Error::InvalidValue { .. })
+    ));
+}
+
+#[test]
+fn enums() {
+    let args = Args::try_parse_from(vec!["rm", "origin", "a", "b", "-f", "3", "-V"]).unwrap();
+    assert_eq!(args.verbose, 1);
+    assert_eq!(
+        args.command,
+        Some(Command::RemoteManage(Remote {
+            name: "origin".to_string(),
+            urls: vec!["a".to_string(), "b".to_string()],
+            depth: 3,
+        }))
+    );
+    let args = Args::try_parse_from(vec!["status"]).unwrap();
+    assert_eq!(args.command, Some(Command::Status));
+    assert_eq!(
+        Args::try_parse_from(vec!["remote_manage", "origin"]),
+        Err(Error::MissingFlag("--fetch-depth".to_string()))
+    );
+    assert_eq!(
+        Args::try_parse_from(vec!["remote_manage", "-f", "1"]),
+        Err(Error::MissingPositional("name".to_string()))
+    );
+}
+
+#[test]
+fn help() {
+    let help = match Args::try_parse_from(vec!["remote_manage", "--help"]) {
+        Err(Error::Help(help)) => help,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert!(help.contains("Manage remotes"));
+    assert!(help.contains("-f, --fetch-depth <DEPTH>"));
+    let help = match Args::try_parse_from(vec!["-h"]) {
+        Err(Error::Help(help)) => help,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert!(help.contains("Copies files around."));
+    assert!(help.contains("-d, --dry-run"));
+    assert!(help.contains("[default: 1]"));
+}
+
+#[test]
+fn strict() {
+    assert_eq!(
+        Single::try_parse_from(vec!["-v", "a"]),
+        Ok(Single {
+            verbose: true,
+            file: Some("a".to_string()),
+        })
+    );
+    assert_eq!(
+        Single::try_parse_from(vec!["--verbsoe", "a"]),
+        Err(Error::Misspelled {
+            given: "--verbsoe".to_string(),
+            suggestion: "--verbose".to_string(),
+        })
+    );
+    assert_eq!(
+        Single::try_parse_from(vec!["-x", "a"]),
+        Err(Error::UnknownFlag("-x".to_string()))
+    );
+    assert_eq!(
+        Single::try_parse_from(vec!["a", "b"]),
+        Err(Error::UnknownArgument("b".to_string()))
+    );
+}
+
+#[test]
+fn global_values() {
+    assert_eq!(
+        Build::try_parse_from(vec!["-I", "a", "run", "-I", "b", "-I", "c"]),
+        Ok(Build {
+            include: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            command: Some(Step::Run),
+        })
+    );
+}